@@ -1,6 +1,6 @@
 //! Test the formatted output
 //!
-//! This example hasn't a special requires
+//! This example uses a HAL built on embedded-hal 0.2, so it needs the `eh0` feature
 #![deny(unsafe_code)]
 #![no_main]
 #![no_std]
@@ -21,7 +21,8 @@ use stm32f7x7_hal::{
 };
 
 use tmc5160::registers::*;
-use tmc5160::{DataPacket, Error, Tmc5160};
+use tmc5160::eh0::Tmc5160;
+use tmc5160::{DataPacket, Error};
 
 #[entry]
 fn main() -> ! {
@@ -58,7 +59,7 @@ fn main() -> ! {
     let spi = Spi::spi1(
         p.SPI1,
         (sck, miso, mosi),
-        tmc5160::MODE,
+        tmc5160::eh0::MODE,
         500.khz().into(),
         clocks,
     );
@@ -66,9 +67,9 @@ fn main() -> ! {
     // set up stepper driver
     let mut stepper_driver = Tmc5160::new(spi, nss);
     // optionally, you could attach an EN pin, which then lets you use the `enable()` and `disable()` functions:
-    // let mut stepper_driver = Tmc5160::new(spi, nss).attach_en(en);
+    // let mut stepper_driver = Tmc5160::new(spi, nss).attach_en(tmc5160::eh0::Pin(en));
     // you could also invert this pin (normally not required):
-    // let mut stepper_driver = Tmc5160::new(spi, nss).attach_en(en).en_inverted(true);
+    // let mut stepper_driver = Tmc5160::new(spi, nss).attach_en(tmc5160::eh0::Pin(en)).en_inverted(true);
 
     // clear G_STAT register
     match stepper_driver.clear_g_stat(){
@@ -80,6 +81,16 @@ fn main() -> ! {
         }
     }
 
+    // check that the chip answers
+    match stepper_driver.identify() {
+        Ok(info) => {
+            sprintln!(in_out, "TMC5160 version {:#x} found, SD_MODE: {}, DRV_ENN: {}", info.version, info.sd_mode, info.drv_enn);
+        }
+        Err(error) => {
+            sprintln!(in_out, "Error identifying the TMC5160 is {:?}", error);
+        }
+    }
+
     // read OFFSET
     match stepper_driver.read_offset() {
         Ok(offset) => {
@@ -0,0 +1,142 @@
+//! Async driver flavour built on [`embedded-hal-async`]
+//!
+//! Enabled with the `async` cargo feature. The API mirrors the blocking [`crate::Tmc5160`], every method
+//! that talks to the chip is `async`. Both drivers are expanded from one implementation, only the transport trait
+//! and the SPI transport are async specific.
+//!
+//! [`embedded-hal-async`]: https://docs.rs/embedded-hal-async/1.0
+//!
+
+use core::result::Result;
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::registers::*;
+use crate::compare::ComparePoints;
+use crate::encoder::{EncoderCalibration, StepLoss, StepLossAction};
+use crate::mslut::MsLut;
+use crate::ramp::RampProfile;
+use crate::shadow::Shadow;
+use crate::units::{Acceleration, Mechanics, Microsteps, Position, Velocity, VelocityRaw};
+use crate::transport::{pipeline, Interface};
+use crate::{conversion, ChipInfo, DataPacket, Error, OtpConfirm, ResetPolicy};
+
+/// Async register access to a TMC5160, the async twin of [`crate::transport::Transport`]
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    /// Error of the underlying bus
+    type Error;
+
+    /// read the register at the given address
+    async fn read_register(&mut self, addr: u8) -> Result<DataPacket, Error<Self::Error>>;
+
+    /// write `val` (MSB first) to the register at the given address
+    async fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<Self::Error>>;
+
+    /// interface the chip is accessed by, the chip answers only on the interface selected by its SPI_MODE pin
+    fn interface(&self) -> Interface {
+        Interface::Spi
+    }
+
+    /// read the registers at the given addresses, transports that can pipeline requests override this
+    async fn read_registers<const N: usize>(&mut self, addrs: [u8; N]) -> Result<[DataPacket; N], Error<Self::Error>> {
+        let mut packets = [DataPacket::EMPTY; N];
+        for (packet, addr) in packets.iter_mut().zip(addrs) {
+            *packet = self.read_register(addr).await?;
+        }
+        Ok(packets)
+    }
+}
+
+/// Async SPI transport, chip select is handled by the [`SpiDevice`]
+pub struct SpiTransport<SPI> {
+    spi: SPI,
+}
+
+impl<SPI, E> SpiTransport<SPI>
+    where
+        SPI: SpiDevice<u8, Error=E>,
+{
+    /// Create a new transport from an async SPI device
+    pub fn new(spi: SPI) -> Self {
+        SpiTransport { spi }
+    }
+
+    /// release the SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    async fn read_io(&mut self, addr: u8) -> Result<DataPacket, Error<E>> {
+        let mut buffer = [addr, 0, 0, 0, 0];
+
+        self.spi.transfer_in_place(&mut buffer).await.map_err(Error::Spi)?;
+
+        let response = buffer;
+
+        Ok(DataPacket {
+            status: SpiStatus::from_bytes([response[0]]),
+            data: u32::from_be_bytes([response[1], response[2], response[3], response[4]]),
+            debug: response,
+        })
+    }
+}
+
+impl<SPI, E> Transport for SpiTransport<SPI>
+    where
+        SPI: SpiDevice<u8, Error=E>,
+{
+    type Error = E;
+
+    async fn read_register(&mut self, addr: u8) -> Result<DataPacket, Error<E>> {
+        // Process cmd to read, return previous (dummy) state
+        let _dummy = self.read_io(addr).await?;
+        // Repeat cmd to read, return state
+        self.read_io(addr).await
+    }
+
+    async fn read_registers<const N: usize>(&mut self, addrs: [u8; N]) -> Result<[DataPacket; N], Error<E>> {
+        let mut packets = [DataPacket::EMPTY; N];
+        for (addr, index) in pipeline(addrs) {
+            let packet = self.read_io(addr).await?;
+            if let Some(index) = index {
+                packets[index] = packet;
+            }
+        }
+        Ok(packets)
+    }
+
+    async fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<E>> {
+        let mut buffer = [addr | 0x80, val[0], val[1], val[2], val[3]];
+
+        let debug_val = buffer;
+
+        self.spi.transfer_in_place(&mut buffer).await.map_err(Error::Spi)?;
+
+        let response = buffer;
+
+        Ok(DataPacket {
+            status: SpiStatus::from_bytes([response[0]]),
+            data: u32::from_be_bytes([response[1], response[2], response[3], response[4]]),
+            debug: debug_val,
+        })
+    }
+}
+
+driver! {
+    /// Async TMC5160 driver
+    async await
+}
+
+impl<SPI, EN, E> Tmc5160<SpiTransport<SPI>, EN>
+    where
+        SPI: SpiDevice<u8, Error=E>,
+        EN: OutputPin,
+{
+    /// Create a new driver from an async SPI device, chip select is handled by the device
+    pub fn new(spi: SPI) -> Self {
+        Self::with_transport(SpiTransport::new(spi))
+    }
+}
@@ -0,0 +1,203 @@
+//! Multiple TMC5160 in a SPI daisy chain
+//!
+//! The devices share one chip select, SDO of each device is connected to SDI of the next one. Every access shifts a
+//! frame of 40·N bits through the whole chain. Position 0 is the device connected to the MOSI line of the
+//! controller, position N - 1 the one connected to MISO.
+//!
+//! ```ignore
+//! let chain: SpiChain<_, 3> = SpiChain::new(spi_device);
+//! let mut x_axis = Tmc5160::with_transport(chain.device(0));
+//! let mut y_axis = Tmc5160::with_transport(chain.device(1));
+//! ```
+
+use core::cell::RefCell;
+use core::result::Result;
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::registers::{Registers, SpiStatus};
+use crate::transport::{read_pipelined, Transport};
+use crate::{DataPacket, Error};
+
+/// datagram sent to devices that are not addressed, a read of GCONF has no side effects
+const NOP: [u8; 5] = [Registers::GCONF as u8, 0, 0, 0, 0];
+
+/// `N` TMC5160 sharing one SPI device
+pub struct SpiChain<SPI, const N: usize> {
+    spi: RefCell<SPI>,
+}
+
+impl<SPI, E, const N: usize> SpiChain<SPI, N>
+    where
+        SPI: SpiDevice<u8, Error=E>,
+{
+    /// Create a new chain of `N` devices from a SPI device
+    pub fn new(spi: SPI) -> Self {
+        SpiChain { spi: RefCell::new(spi) }
+    }
+
+    /// release the SPI device
+    pub fn release(self) -> SPI {
+        self.spi.into_inner()
+    }
+
+    /// number of devices in the chain
+    pub const LEN: usize = N;
+
+    /// handle of the device at `position`, it can be used as transport of a [`crate::Tmc5160`]
+    pub fn device(&self, position: usize) -> ChainDevice<'_, SPI, N> {
+        assert!(position < N, "position is outside of the chain");
+        ChainDevice { chain: self, position }
+    }
+
+    /// shift one datagram per device through the chain, indexed by position
+    ///
+    /// Every device answers with its status and the data requested by its previous datagram.
+    pub fn transfer(&self, datagrams: [[u8; 5]; N]) -> Result<[DataPacket; N], Error<E>> {
+        let responses = self.shift(datagrams)?;
+        Ok(core::array::from_fn(|position| packet(responses[position])))
+    }
+
+    fn shift(&self, datagrams: [[u8; 5]; N]) -> Result<[[u8; 5]; N], Error<E>> {
+        // the datagram for the last device has to be shifted out first
+        let mut frame = datagrams;
+        frame.reverse();
+
+        self.spi.borrow_mut().transfer_in_place(frame.as_flattened_mut()).map_err(Error::Spi)?;
+
+        frame.reverse();
+        Ok(frame)
+    }
+
+    fn transfer_single(&self, position: usize, datagram: [u8; 5]) -> Result<DataPacket, Error<E>> {
+        let mut datagrams = [NOP; N];
+        datagrams[position] = datagram;
+        let responses = self.shift(datagrams)?;
+        Ok(packet(responses[position]))
+    }
+}
+
+fn packet(response: [u8; 5]) -> DataPacket {
+    DataPacket {
+        status: SpiStatus::from_bytes([response[0]]),
+        data: u32::from_be_bytes([response[1], response[2], response[3], response[4]]),
+        debug: response,
+    }
+}
+
+/// Transport for a single device of a [`SpiChain`], all other devices receive a no-op read
+pub struct ChainDevice<'a, SPI, const N: usize> {
+    chain: &'a SpiChain<SPI, N>,
+    position: usize,
+}
+
+impl<SPI, const N: usize> ChainDevice<'_, SPI, N> {
+    /// position of the device in the chain
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<SPI, E, const N: usize> Transport for ChainDevice<'_, SPI, N>
+    where
+        SPI: SpiDevice<u8, Error=E>,
+{
+    type Error = E;
+
+    fn read_register(&mut self, addr: u8) -> Result<DataPacket, Error<E>> {
+        let datagram = [addr & 0x7F, 0, 0, 0, 0];
+        // Process cmd to read, return previous (dummy) state
+        let _dummy = self.chain.transfer_single(self.position, datagram)?;
+        // Repeat cmd to read, return state
+        self.chain.transfer_single(self.position, datagram)
+    }
+
+    fn read_registers<const M: usize>(&mut self, addrs: [u8; M]) -> Result<[DataPacket; M], Error<E>> {
+        read_pipelined(addrs, |addr| self.chain.transfer_single(self.position, [addr & 0x7F, 0, 0, 0, 0]))
+    }
+
+    fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<E>> {
+        let datagram = [addr | 0x80, val[0], val[1], val[2], val[3]];
+        let mut packet = self.chain.transfer_single(self.position, datagram)?;
+        packet.debug = datagram;
+        Ok(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::convert::Infallible;
+    use std::vec::Vec;
+
+    use embedded_hal::spi::{ErrorType, Operation};
+
+    use super::*;
+
+    /// three devices as shift registers, every device answers with its position and the address it received last
+    struct Chain {
+        received: [[u8; 5]; 3],
+        frames: Vec<Vec<u8>>,
+    }
+
+    impl ErrorType for Chain {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice<u8> for Chain {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            for operation in operations {
+                if let Operation::TransferInPlace(frame) = operation {
+                    self.frames.push(frame.to_vec());
+                    // the device at MISO shifts out first
+                    let mut out = Vec::new();
+                    for position in (0..3).rev() {
+                        out.extend_from_slice(&[position as u8, 0, 0, 0, self.received[position][0] & 0x7F]);
+                    }
+                    // the first datagram ends up in the device at MISO
+                    for position in 0..3 {
+                        let start = (2 - position) * 5;
+                        self.received[position].copy_from_slice(&frame[start..start + 5]);
+                    }
+                    frame.copy_from_slice(&out);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn chain() -> SpiChain<Chain, 3> {
+        SpiChain::new(Chain { received: [NOP; 3], frames: Vec::new() })
+    }
+
+    #[test]
+    fn write_reaches_its_device() {
+        let chain = chain();
+        chain.device(0).write_register(0x6C, [1, 2, 3, 4]).unwrap();
+        let spi = chain.release();
+        assert_eq!(spi.frames.len(), 1);
+        assert_eq!(spi.frames[0].len(), 15);
+        assert_eq!(spi.received, [[0xEC, 1, 2, 3, 4], NOP, NOP]);
+    }
+
+    #[test]
+    fn read_is_answered_by_its_device() {
+        let chain = chain();
+        let packet = chain.device(1).read_register(0x21).unwrap();
+        assert_eq!(packet.debug[0], 1);
+        assert_eq!(packet.data, 0x21);
+        assert_eq!(chain.release().received, [NOP, [0x21, 0, 0, 0, 0], NOP]);
+    }
+
+    #[test]
+    fn transfer_is_indexed_by_position() {
+        let chain = chain();
+        chain.transfer([[0x21, 0, 0, 0, 0], [0x22, 0, 0, 0, 0], [0x23, 0, 0, 0, 0]]).unwrap();
+        let packets = chain.transfer([NOP; 3]).unwrap();
+        for (position, packet) in packets.iter().enumerate() {
+            assert_eq!(packet.debug[0], position as u8);
+            assert_eq!(packet.data, 0x21 + position as u32);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Position compare output
+//!
+//! The TMC5160 outputs a pulse on DIAG1 when XACTUAL passes X_COMPARE. [`ComparePoints`] holds a list of positions
+//! in any unit of [`crate::units`] that are armed one after the other by [`crate::Tmc5160::rearm_compare`], e.g. to
+//! trigger a camera at several positions of one move:
+//!
+//! ```ignore
+//! stepper_driver.route_compare_to_diag1(true)?;
+//! let mut points = ComparePoints::new(&[10.0, 20.0, 30.0]);
+//! stepper_driver.move_to(40.0)?;
+//! while !points.is_done() {
+//!     stepper_driver.rearm_compare(&mut points)?;
+//! }
+//! ```
+
+/// positions that are armed as compare point one after the other, `f32` and `i32` are full steps
+pub struct ComparePoints<'a, P = f32> {
+    pub(crate) positions: &'a [P],
+    /// index of the next point to arm or the armed point
+    pub(crate) next: usize,
+    /// XACTUAL was below the armed point when it was armed, `None` if no point is armed
+    pub(crate) armed_below: Option<bool>,
+}
+
+impl<'a, P> ComparePoints<'a, P> {
+    /// Create a list of compare points in the order the motor passes them
+    pub fn new(positions: &'a [P]) -> Self {
+        ComparePoints { positions, next: 0, armed_below: None }
+    }
+
+    /// all points have been passed
+    pub fn is_done(&self) -> bool {
+        self.next >= self.positions.len()
+    }
+
+    /// number of points that have not been passed yet
+    pub fn remaining(&self) -> usize {
+        self.positions.len().saturating_sub(self.next)
+    }
+
+    /// the armed point has been passed at `x_actual`, move on to the next one
+    pub(crate) fn passed(&mut self, x_actual: i32, target: i32) -> bool {
+        let passed = match self.armed_below {
+            Some(true) => x_actual >= target,
+            Some(false) => x_actual <= target,
+            None => false,
+        };
+        if passed {
+            self.next += 1;
+            self.armed_below = None;
+        }
+        passed
+    }
+}
@@ -0,0 +1,65 @@
+//! Unit conversion shared by the blocking and the async driver
+
+use crate::units::Microsteps;
+
+/// convert a velocity in Hz to the VMAX register value
+pub(crate) fn speed_from_hz(clock: f32, step_count: f32, speed_hz: f32) -> u32 {
+    (speed_hz / (clock / 16_777_216.0) * step_count) as u32
+}
+
+/// convert a VMAX register value to a velocity in Hz
+pub(crate) fn speed_to_hz(clock: f32, step_count: f32, speed_raw: u32) -> f32 {
+    speed_raw as f32 / step_count * (clock / 16_777_216.0)
+}
+
+/// convert an acceleration in Hz/s to the AMAX register value
+pub(crate) fn accel_from_hz(clock: f32, step_count: f32, accel_hz_per_s: f32) -> u32 {
+    (accel_hz_per_s / (clock * clock)
+        * (512.0 * 256.0)
+        * 16_777_216.0
+        * step_count) as u32
+}
+
+/// convert a position in microsteps to steps
+pub(crate) fn position_from_raw(step_count: f32, position: Microsteps) -> f32 {
+    (position.0 as f64 / step_count as f64) as f32
+}
+
+/// round up to the next integer
+pub(crate) fn ceil(val: f32) -> f32 {
+    let truncated = val as i32 as f32;
+    if truncated < val { truncated + 1.0 } else { truncated }
+}
+
+/// rescale a position to another microstep resolution, rounded to the nearest microstep
+///
+/// Returns `None` if the position does not fit 32 bits in the new resolution.
+pub(crate) fn rescale(position: Microsteps, from: u32, to: u32) -> Option<Microsteps> {
+    let scaled = position.0 as i64 * to as i64;
+    let half = from as i64 / 2;
+    let rounded = if scaled < 0 { scaled - half } else { scaled + half };
+    i32::try_from(rounded / from as i64).ok().map(Microsteps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rescale_rounds_to_the_nearest_microstep() {
+        assert_eq!(rescale(Microsteps(2560), 256, 16), Some(Microsteps(160)));
+        assert_eq!(rescale(Microsteps(-2560), 256, 16), Some(Microsteps(-160)));
+        assert_eq!(rescale(Microsteps(8), 256, 16), Some(Microsteps(1)));
+        assert_eq!(rescale(Microsteps(-8), 256, 16), Some(Microsteps(-1)));
+        assert_eq!(rescale(Microsteps(7), 256, 16), Some(Microsteps(0)));
+        assert_eq!(rescale(Microsteps(-3), 1, 256), Some(Microsteps(-768)));
+    }
+
+    #[test]
+    fn rescale_out_of_range() {
+        assert_eq!(rescale(Microsteps(i32::MAX / 16), 16, 256), Some(Microsteps(i32::MAX / 16 * 16)));
+        assert_eq!(rescale(Microsteps(i32::MAX / 16 + 1), 16, 256), None);
+        assert_eq!(rescale(Microsteps(i32::MIN / 16), 16, 256), Some(Microsteps(i32::MIN)));
+        assert_eq!(rescale(Microsteps(i32::MIN / 16 - 1), 16, 256), None);
+    }
+}
@@ -0,0 +1,1423 @@
+//! Driver implementation shared by the blocking [`crate::Tmc5160`] and the async [`crate::asynch::Tmc5160`]
+//!
+//! `driver!` expands to the driver struct and its methods in the invoking module. Names like `Transport` and
+//! `DelayNs` are resolved there, the async flavour passes `async await` to make every bus access awaited.
+
+macro_rules! driver {
+    ($(#[$attr:meta])* $($async:ident $await:ident)?) => {
+        $(#[$attr])*
+        pub struct Tmc5160<BUS, EN> {
+            bus: BUS,
+            en: Option<EN>,
+            /// the max velocity that is set
+            pub v_max: f32,
+            /// status register of the driver
+            pub status: SpiStatus,
+            /// debug info of the last transmission
+            pub debug: [u8; 5],
+            /// number of chip resets the driver has recovered from
+            pub resets: u32,
+            _clock: u32,
+            _step_count: f32,
+            _en_inverted: bool,
+            _reset_policy: ResetPolicy,
+            _reset_poll: u32,
+            _sd_mode: bool,
+            accesses: u32,
+            mechanics: Mechanics,
+            /// value of the GCONF register
+            pub g_conf: GConf,
+            /// value of the NODECONF register
+            pub node_conf: NodeConf,
+            /// value of the OTPPROG register
+            pub otp_prog: OtpProg,
+            /// value of the SHORT_CONF register
+            pub short_conf: ShortConf,
+            /// value of the DRV_CONF register
+            pub drv_conf: DrvConf,
+            /// value of the IHOLD_IRUN register
+            pub ihold_irun: IHoldIRun,
+            /// value of the SWMODE register
+            pub sw_mode: SwMode,
+            /// value of the ENCMODE register
+            pub enc_mode: EncMode,
+            /// value of the MSLUTSEL register
+            pub ms_lut_sel: MsLutSel,
+            /// value of the CHOPCONF register
+            pub chop_conf: ChopConf,
+            /// value of the COOLCONF register
+            pub cool_conf: CoolConf,
+            /// value of the PWMCONF register
+            pub pwm_conf: PwmConf,
+            shadow: Shadow,
+        }
+
+        impl<BUS, EN, E> Tmc5160<BUS, EN>
+            where
+                BUS: Transport<Error=E>,
+                EN: OutputPin,
+        {
+            /// Create a new driver on top of a register transport
+            pub fn with_transport(bus: BUS) -> Self {
+                Tmc5160 {
+                    bus,
+                    en: None,
+                    v_max: 0.0,
+                    status: SpiStatus::new(),
+                    debug: [0; 5],
+                    resets: 0,
+                    _clock: 12_000_000,
+                    _step_count: 256.0,
+                    _en_inverted: false,
+                    _reset_policy: ResetPolicy::Ignore,
+                    _reset_poll: 0,
+                    _sd_mode: false,
+                    accesses: 0,
+                    mechanics: Mechanics::default(),
+                    g_conf: GConf::new(),
+                    node_conf: NodeConf::new(),
+                    otp_prog: OtpProg::new(),
+                    short_conf: ShortConf::new(),
+                    drv_conf: DrvConf::new(),
+                    ihold_irun: IHoldIRun::new(),
+                    sw_mode: SwMode::new(),
+                    enc_mode: EncMode::new(),
+                    ms_lut_sel: MsLutSel::new(),
+                    chop_conf: ChopConf::new(),
+                    cool_conf: CoolConf::new(),
+                    pwm_conf: PwmConf::new(),
+                    shadow: Shadow::new(),
+                }
+            }
+
+            /// attach an enable pin to the driver
+            pub fn attach_en(mut self, en: EN) -> Self {
+                self.en = Some(en);
+                self
+            }
+
+            /// invert the enable pin
+            pub fn en_inverted(mut self, inv: bool) -> Self {
+                self._en_inverted = inv;
+                self
+            }
+
+            /// specify the reaction on a reset of the chip (Default is [`ResetPolicy::Ignore`])
+            pub fn reset_policy(mut self, policy: ResetPolicy) -> Self {
+                self._reset_policy = policy;
+                self
+            }
+
+            /// on UART read GSTAT every `accesses` register accesses to detect a reset, the replies carry no reset flag
+            /// (Default is 0, no polling)
+            ///
+            /// Polling costs one extra request and reply and is skipped with [`ResetPolicy::Ignore`].
+            pub fn reset_poll(mut self, accesses: u32) -> Self {
+                self._reset_poll = accesses;
+                self
+            }
+
+            /// the chip is driven by STEP/DIR (SD_MODE high), positions are not rescaled when MRES changes
+            pub(crate) fn sd_mode(mut self) -> Self {
+                self._sd_mode = true;
+                self
+            }
+
+            /// specify clock speed of the Tmc5160 (Default is 12 MHz)
+            pub fn clock(mut self, clock: f32) -> Self {
+                self._clock = clock as u32;
+                self
+            }
+
+            /// specify clock speed of the Tmc5160 in Hz without a float conversion (Default is 12 MHz)
+            pub fn clock_hz(mut self, clock: u32) -> Self {
+                self._clock = clock;
+                self
+            }
+
+            /// specify step count of the motor (Default is 256), this sets MRES of `chop_conf` and the step count follows
+            /// MRES whenever CHOPCONF is written
+            ///
+            /// # Panics
+            ///
+            /// if `step_count` is not a microstep resolution of the chip (1, 2, 4, .., 256)
+            pub fn step_count(mut self, step_count: f32) -> Self {
+                let microsteps = step_count as u32;
+                assert!(microsteps as f32 == step_count, "step count is not a microstep resolution");
+                self.select_resolution(microsteps);
+                self
+            }
+
+            /// specify the motor and transmission for the physical units, this sets MRES of `chop_conf` to `microsteps`
+            ///
+            /// # Panics
+            ///
+            /// if `microsteps` is not a microstep resolution of the chip (1, 2, 4, .., 256)
+            pub fn mechanics(mut self, mechanics: Mechanics) -> Self {
+                self.mechanics = mechanics;
+                self.select_resolution(mechanics.microsteps);
+                self
+            }
+
+            /// set MRES of `chop_conf` and the microsteps of the unit conversions before CHOPCONF is written
+            fn select_resolution(&mut self, microsteps: u32) {
+                let resolution = MicrostepResolution::from_microsteps(microsteps);
+                self.chop_conf.set_resolution(resolution.expect("step count is not a microstep resolution"));
+                self.set_microsteps(microsteps);
+            }
+
+            /// get the motor and transmission of the physical units
+            pub fn get_mechanics(&self) -> &Mechanics {
+                &self.mechanics
+            }
+
+            /// release the transport
+            pub fn release(self) -> BUS {
+                self.bus
+            }
+
+            /// read a specified register, fails with [`Error::AccessMode`] if the register is write only
+            pub $($async)? fn read_register<T>(&mut self, reg: T) -> Result<DataPacket, Error<E>>
+                where
+                    T: Address + Copy,
+            {
+                if !reg.access().readable() {
+                    return Err(Error::AccessMode);
+                }
+                if reg.addr() != Registers::GSTAT.addr() {
+                    self.poll_reset()$(.$await)??;
+                }
+                let packet = self.bus.read_register(reg.addr())$(.$await)??;
+                let gstat_reset = reg.addr() == Registers::GSTAT.addr() && GStat::from_u32(packet.data).reset();
+                self.check_reset(packet.status.reset_flag() || gstat_reset)$(.$await)??;
+                Ok(packet)
+            }
+
+            /// read several registers at once
+            ///
+            /// On SPI every frame returns the data requested by the previous one, so the reads are pipelined and
+            /// take N + 1 frames instead of 2·N.
+            pub $($async)? fn read_many<T, const N: usize>(&mut self, regs: [T; N]) -> Result<[DataPacket; N], Error<E>>
+                where
+                    T: Address + Copy,
+            {
+                if regs.iter().any(|reg| !reg.access().readable()) {
+                    return Err(Error::AccessMode);
+                }
+                self.poll_reset()$(.$await)??;
+                let packets = self.bus.read_registers(regs.map(|reg| reg.addr()))$(.$await)??;
+                if let Some(packet) = packets.last() {
+                    self.status = packet.status;
+                }
+                self.check_reset(packets.iter().any(|packet| packet.status.reset_flag()))$(.$await)??;
+                Ok(packets)
+            }
+
+            /// read a typed register
+            pub $($async)? fn read<R>(&mut self) -> Result<R, Error<E>>
+                where
+                    R: Readable,
+            {
+                let packet = self.read_register(R::ADDR)$(.$await)??;
+                self.status = packet.status;
+                self.debug = packet.debug;
+                Ok(R::from_u32(packet.data))
+            }
+
+            /// write a typed register
+            pub $($async)? fn write<R>(&mut self, reg: &R) -> Result<DataPacket, Error<E>>
+                where
+                    R: Writable,
+            {
+                let mut value = reg.to_u32().to_be_bytes();
+                let packet = self.write_register(R::ADDR, &mut value)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// write value to a specified register, fails with [`Error::AccessMode`] if the register is read only
+            pub $($async)? fn write_register<T>(&mut self, reg: T, val: &mut [u8; 4]) -> Result<DataPacket, Error<E>>
+                where
+                    T: Address + Copy,
+            {
+                if !reg.access().writable() {
+                    return Err(Error::AccessMode);
+                }
+                if reg.addr() != Registers::GSTAT.addr() {
+                    self.poll_reset()$(.$await)??;
+                }
+                let resolution = if reg.addr() == Registers::CHOPCONF.addr() {
+                    self.change_resolution(u32::from_be_bytes(*val))$(.$await)??
+                } else {
+                    None
+                };
+                let packet = self.bus.write_register(reg.addr(), *val)$(.$await)??;
+                self.written(reg.addr(), u32::from_be_bytes(*val));
+                if let Some(microsteps) = resolution {
+                    self.set_microsteps(microsteps);
+                }
+                // clearing GSTAT is answered with the reset flag that is being cleared
+                if reg.addr() != Registers::GSTAT.addr() {
+                    self.check_reset(packet.status.reset_flag())$(.$await)??;
+                }
+                Ok(packet)
+            }
+
+            /// shadow copy of the configuration registers
+            pub fn shadow(&self) -> &Shadow {
+                &self.shadow
+            }
+
+            /// stage a typed register, it is written by the next [`Self::commit`] if the value changed
+            ///
+            /// Returns false if the register is not part of the shadow.
+            pub fn stage<R>(&mut self, reg: &R) -> bool
+                where
+                    R: Writable,
+            {
+                self.stage_raw(R::ADDR, reg.to_u32())
+            }
+
+            /// stage a value for a specified register, it is written by the next [`Self::commit`] if the value changed
+            ///
+            /// Returns false if the register is not part of the shadow.
+            pub fn stage_raw<T>(&mut self, reg: T, val: u32) -> bool
+                where
+                    T: Address + Copy,
+            {
+                let staged = self.shadow.set(reg.addr(), val);
+                if staged {
+                    self.sync_field(reg.addr(), val);
+                }
+                staged
+            }
+
+            /// write all staged registers and changed register fields (`g_conf`, `chop_conf`, ...) to the chip
+            ///
+            /// Registers are written in an order that enables the chopper and starts the ramp generator last.
+            /// Returns the number of written registers.
+            pub $($async)? fn commit(&mut self) -> Result<usize, Error<E>> {
+                self.stage_fields();
+                let (count, reset) = self.write_dirty()$(.$await)??;
+                self.check_reset(reset)$(.$await)??;
+                Ok(count)
+            }
+
+            /// write all known configuration registers again, e.g. after the chip has been reset
+            ///
+            /// Returns the number of written registers.
+            pub $($async)? fn resync(&mut self) -> Result<usize, Error<E>> {
+                self.stage_fields();
+                self.shadow.invalidate();
+                self.commit()$(.$await)?
+            }
+
+            /// restore the chip after a reset: clear GSTAT and write all known configuration registers again
+            ///
+            /// Positions are not restored, XACTUAL and XTARGET are 0 after a reset. Returns the number of written registers.
+            pub $($async)? fn recover(&mut self) -> Result<usize, Error<E>> {
+                self.bus.write_register(Registers::GSTAT.addr(), 0b111_u32.to_be_bytes())$(.$await)??;
+                self.resets = self.resets.wrapping_add(1);
+                self.stage_fields();
+                self.shadow.invalidate();
+                let (count, _) = self.write_dirty()$(.$await)??;
+                Ok(count)
+            }
+
+            /// write all dirty registers of the shadow, returns the number of written registers and if a reset was seen
+            $($async)? fn write_dirty(&mut self) -> Result<(usize, bool), Error<E>> {
+                let mut count = 0;
+                let mut reset = false;
+                while let Some((addr, val)) = self.shadow.next_dirty() {
+                    let resolution = if addr == Registers::CHOPCONF.addr() {
+                        self.change_resolution(val)$(.$await)??
+                    } else {
+                        None
+                    };
+                    let packet = self.bus.write_register(addr, val.to_be_bytes())$(.$await)??;
+                    if let Some(microsteps) = resolution {
+                        self.set_microsteps(microsteps);
+                    }
+                    self.status = packet.status;
+                    reset |= packet.status.reset_flag();
+                    self.shadow.written(addr, val);
+                    count += 1;
+                }
+                Ok((count, reset))
+            }
+
+            /// rescale the positions and the encoder settings before a CHOPCONF value with another MRES is written
+            ///
+            /// XACTUAL, XTARGET and X_ENC are read and rewritten in hold mode so the ramp generator does not start to
+            /// move, the known values of X_COMPARE, ENC_CONST and ENC_DEVIATION are rewritten from the shadow.
+            /// Returns the new microsteps per full step if MRES changes, they are applied with [`Self::set_microsteps`] once
+            /// CHOPCONF has been written. Fails with [`Error::Moving`] if the motor is not at standstill and with
+            /// [`Error::OutOfRange`] for a reserved MRES or a value that does not fit the new resolution, nothing is
+            /// written then.
+            $($async)? fn change_resolution(&mut self, chop_conf: u32) -> Result<Option<u32>, Error<E>> {
+                let microsteps = ChopConf::from_u32(chop_conf).resolution().ok_or(Error::OutOfRange)?.microsteps();
+                let previous = self.mechanics.microsteps;
+                if microsteps == previous {
+                    return Ok(None);
+                }
+                // with SD_MODE high the ramp generator is off and the step generator counts the position
+                if previous == 0 || self._sd_mode {
+                    return Ok(Some(microsteps));
+                }
+                let rescale = |value: u32| {
+                    conversion::rescale(Microsteps(value as i32), previous, microsteps).map(|position| position.0 as u32)
+                };
+                let decimal = self.enc_mode.enc_sel_decimal();
+                let settings = [
+                    (Registers::X_COMPARE, self.shadow.get_raw(Registers::X_COMPARE).map(rescale)),
+                    (Registers::ENC_CONST, self.shadow.get_raw(Registers::ENC_CONST).map(|val| {
+                        EncConst::from_u32(val).rescale(microsteps as i32, previous as i32, decimal).map(|c| c.to_u32())
+                    })),
+                    (Registers::ENC_DEVIATION, self.shadow.get_raw(Registers::ENC_DEVIATION).map(|val| {
+                        rescale(val).filter(|&deviation| deviation <= 0xF_FFFF)
+                    })),
+                ];
+                if settings.iter().any(|&(_, value)| value == Some(None)) {
+                    return Err(Error::OutOfRange);
+                }
+                if !self.g_conf.direct_mode() {
+                    let v_actual = self.bus.read_register(Registers::VACTUAL.addr())$(.$await)??.data;
+                    if VelocityRaw::from_vactual(v_actual).0 != 0 {
+                        return Err(Error::Moving);
+                    }
+                    let mut positions = [(Registers::XACTUAL, 0), (Registers::XTARGET, 0), (Registers::X_ENC, 0)];
+                    for (reg, position) in positions.iter_mut() {
+                        let current = self.bus.read_register(reg.addr())$(.$await)??.data;
+                        *position = rescale(current).ok_or(Error::OutOfRange)?;
+                    }
+                    let ramp_mode = self.shadow.get_raw(Registers::RAMPMODE).unwrap_or(RampMode::PositioningMode as u32);
+                    self.bus.write_register(Registers::RAMPMODE.addr(), (RampMode::HoldMode as u32).to_be_bytes())$(.$await)??;
+                    for (reg, position) in positions {
+                        self.bus.write_register(reg.addr(), position.to_be_bytes())$(.$await)??;
+                    }
+                    self.bus.write_register(Registers::RAMPMODE.addr(), ramp_mode.to_be_bytes())$(.$await)??;
+                }
+                for (reg, value) in settings {
+                    if let Some(Some(value)) = value {
+                        self.bus.write_register(reg.addr(), value.to_be_bytes())$(.$await)??;
+                        self.shadow.written(reg.addr(), value);
+                    }
+                }
+                Ok(Some(microsteps))
+            }
+
+            /// follow a new MRES after CHOPCONF has been written
+            fn set_microsteps(&mut self, microsteps: u32) {
+                self.mechanics.microsteps = microsteps;
+                self._step_count = microsteps as f32;
+            }
+
+            /// read GSTAT on UART every `reset_poll` accesses, the replies do not carry the reset flag
+            $($async)? fn poll_reset(&mut self) -> Result<(), Error<E>> {
+                if self._reset_policy == ResetPolicy::Ignore
+                    || self._reset_poll == 0
+                    || self.bus.interface() != Interface::Uart
+                {
+                    return Ok(());
+                }
+                self.accesses += 1;
+                if self.accesses < self._reset_poll {
+                    return Ok(());
+                }
+                self.accesses = 0;
+                let gstat = self.bus.read_register(Registers::GSTAT.addr())$(.$await)??;
+                self.check_reset(GStat::from_u32(gstat.data).reset())$(.$await)?
+            }
+
+            /// react on a reset of the chip according to the reset policy
+            $($async)? fn check_reset(&mut self, reset: bool) -> Result<(), Error<E>> {
+                if !reset {
+                    return Ok(());
+                }
+                match self._reset_policy {
+                    ResetPolicy::Ignore => Ok(()),
+                    ResetPolicy::Report => Err(Error::Reset),
+                    ResetPolicy::Restore => self.recover()$(.$await)?.map(|_| ()),
+                }
+            }
+
+            /// upload a microstep table to MSLUT0..7, MSLUTSEL and MSLUTSTART
+            pub $($async)? fn write_ms_lut(&mut self, ms_lut: &MsLut) -> Result<DataPacket, Error<E>> {
+                for (offset, word) in ms_lut.words.iter().enumerate() {
+                    let addr = Registers::MSLUT_0_7.addr() + offset as u8;
+                    let packet = self.bus.write_register(addr, word.to_be_bytes())$(.$await)??;
+                    self.written(addr, *word);
+                    self.check_reset(packet.status.reset_flag())$(.$await)??;
+                }
+                self.write(&ms_lut.sel)$(.$await)??;
+                self.write(&ms_lut.start)$(.$await)?
+            }
+
+            /// record a written value in the shadow and the register fields
+            fn written(&mut self, addr: u8, val: u32) {
+                self.shadow.written(addr, val);
+                self.sync_field(addr, val);
+            }
+
+            /// stage the register fields that have been changed since they were last written
+            fn stage_fields(&mut self) {
+                self.shadow.stage(Registers::GCONF.addr(), self.g_conf.to_u32());
+                self.shadow.stage(Registers::SHORT_CONF.addr(), self.short_conf.to_u32());
+                self.shadow.stage(Registers::DRV_CONF.addr(), self.drv_conf.to_u32());
+                self.shadow.stage(Registers::IHOLD_IRUN.addr(), self.ihold_irun.to_u32());
+                self.shadow.stage(Registers::SW_MODE.addr(), self.sw_mode.to_u32());
+                self.shadow.stage(Registers::ENCMODE.addr(), self.enc_mode.to_u32());
+                self.shadow.stage(Registers::MSLUTSEL.addr(), self.ms_lut_sel.to_u32());
+                self.shadow.stage(Registers::COOLCONF.addr(), self.cool_conf.to_u32());
+                self.shadow.stage(Registers::PWMCONF.addr(), self.pwm_conf.to_u32());
+                self.shadow.stage(Registers::CHOPCONF.addr(), self.chop_conf.to_u32());
+            }
+
+            /// keep the register field of a shadowed register in line with the shadow
+            fn sync_field(&mut self, addr: u8, val: u32) {
+                match addr & 0x7F {
+                    a if a == Registers::GCONF.addr() => self.g_conf = GConf::from_u32(val),
+                    a if a == Registers::SHORT_CONF.addr() => self.short_conf = ShortConf::from_u32(val),
+                    a if a == Registers::DRV_CONF.addr() => self.drv_conf = DrvConf::from_u32(val),
+                    a if a == Registers::IHOLD_IRUN.addr() => self.ihold_irun = IHoldIRun::from_u32(val),
+                    a if a == Registers::SW_MODE.addr() => self.sw_mode = SwMode::from_u32(val),
+                    a if a == Registers::ENCMODE.addr() => self.enc_mode = EncMode::from_u32(val),
+                    a if a == Registers::MSLUTSEL.addr() => self.ms_lut_sel = MsLutSel::from_u32(val),
+                    a if a == Registers::COOLCONF.addr() => self.cool_conf = CoolConf::from_u32(val),
+                    a if a == Registers::PWMCONF.addr() => self.pwm_conf = PwmConf::from_u32(val),
+                    a if a == Registers::CHOPCONF.addr() => self.chop_conf = ChopConf::from_u32(val),
+                    _ => {}
+                }
+            }
+
+            /// enable the motor if the EN pin was specified
+            pub fn enable(&mut self) -> Result<(), Error<E>> {
+                if let Some(pin) = &mut self.en {
+                    if self._en_inverted {
+                        pin.set_high().map_err(|_| Error::PinError)
+                    } else {
+                        pin.set_low().map_err(|_| Error::PinError)
+                    }
+                } else {
+                    Ok(())
+                }
+            }
+
+            /// disable the motor if the EN pin was specified
+            pub fn disable(&mut self) -> Result<(), Error<E>> {
+                if let Some(pin) = &mut self.en {
+                    if self._en_inverted {
+                        pin.set_low().map_err(|_| Error::PinError)
+                    } else {
+                        pin.set_high().map_err(|_| Error::PinError)
+                    }
+                } else {
+                    Ok(())
+                }
+            }
+
+            /// clear G_STAT register
+            pub $($async)? fn clear_g_stat(&mut self) -> Result<DataPacket, Error<E>> {
+                let mut value = 0b111_u32.to_be_bytes();
+                //let mut value= (!0_u32).to_be_bytes();
+                self.write_register(Registers::GSTAT, &mut value)$(.$await)?
+            }
+
+            /// clear ENC_STATUS register
+            pub $($async)? fn clear_enc_status(&mut self) -> Result<DataPacket, Error<E>> {
+                let mut value = 0b111_u32.to_be_bytes();
+                //let mut value= (!0_u32).to_be_bytes();
+                self.write_register(Registers::ENC_STATUS, &mut value)$(.$await)?
+            }
+
+            /// write value to SLAVECONF register (node address and send delay in UART mode)
+            pub $($async)? fn update_node_conf(&mut self) -> Result<DataPacket, Error<E>> {
+                let node_conf = self.node_conf;
+                self.write(&node_conf)$(.$await)?
+            }
+
+            /// write value to SW_MODE register
+            pub $($async)? fn update_sw_mode(&mut self) -> Result<DataPacket, Error<E>> {
+                let sw_mode = self.sw_mode;
+                self.write(&sw_mode)$(.$await)?
+            }
+
+            /// write value to G_CONF register
+            pub $($async)? fn update_g_conf(&mut self) -> Result<DataPacket, Error<E>> {
+                let g_conf = self.g_conf;
+                self.write(&g_conf)$(.$await)?
+            }
+
+            /// write value to CHOP_CONF register
+            pub $($async)? fn update_chop_conf(&mut self) -> Result<DataPacket, Error<E>> {
+                let chop_conf = self.chop_conf;
+                self.write(&chop_conf)$(.$await)?
+            }
+
+            /// write value to COOL_CONF register
+            pub $($async)? fn update_cool_conf(&mut self) -> Result<DataPacket, Error<E>> {
+                let cool_conf = self.cool_conf;
+                self.write(&cool_conf)$(.$await)?
+            }
+
+            /// write value to IHOLD_IRUN register
+            pub $($async)? fn update_ihold_irun(&mut self) -> Result<DataPacket, Error<E>> {
+                let ihold_irun = self.ihold_irun;
+                self.write(&ihold_irun)$(.$await)?
+            }
+
+            /// write value to PWM_CONF register
+            pub $($async)? fn update_pwm_conf(&mut self) -> Result<DataPacket, Error<E>> {
+                let pwm_conf = self.pwm_conf;
+                self.write(&pwm_conf)$(.$await)?
+            }
+
+            /// write value to ENC_MODE register
+            pub $($async)? fn update_enc_mode(&mut self) -> Result<DataPacket, Error<E>> {
+                let enc_mode = self.enc_mode;
+                self.write(&enc_mode)$(.$await)?
+            }
+
+            /// write value to GLOBALSCALER register
+            pub $($async)? fn set_global_scaler(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::GLOBALSCALER, &mut value)$(.$await)?
+            }
+
+            /// write value to TPOWERDOWN register
+            pub $($async)? fn set_tpowerdown(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::TPOWERDOWN, &mut value)$(.$await)?
+            }
+
+            /// write value to TPWMTHRS register
+            pub $($async)? fn set_tpwmthrs(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::TPWMTHRS, &mut value)$(.$await)?
+            }
+
+            /// write value to TCOOLTHRS register
+            pub $($async)? fn set_tcoolthrs(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::TCOOLTHRS, &mut value)$(.$await)?
+            }
+
+            /// write value to A1 register
+            pub $($async)? fn set_a1(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::A1, &mut value)$(.$await)?
+            }
+
+            /// write value to V1 register
+            pub $($async)? fn set_v1(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::V1, &mut value)$(.$await)?
+            }
+
+            /// write value to AMAX register
+            pub $($async)? fn set_amax(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::AMAX, &mut value)$(.$await)?
+            }
+
+            /// write value to VMAX register
+            pub $($async)? fn set_vmax(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::VMAX, &mut value)$(.$await)?
+            }
+
+            /// write value to DMAX register
+            pub $($async)? fn set_dmax(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::DMAX, &mut value)$(.$await)?
+            }
+
+            /// write value to D1 register
+            pub $($async)? fn set_d1(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::D1, &mut value)$(.$await)?
+            }
+
+            /// write value to VSTART register
+            pub $($async)? fn set_vstart(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::VSTART, &mut value)$(.$await)?
+            }
+
+            /// write value to VSTOP register
+            pub $($async)? fn set_vstop(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::VSTOP, &mut value)$(.$await)?
+            }
+
+            /// write value to X_COMPARE register
+            pub $($async)? fn set_x_compare(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::X_COMPARE, &mut value)$(.$await)?
+            }
+
+            /// write value to VDCMIN register
+            pub $($async)? fn set_vdcmin(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+                let mut value = val.to_be_bytes();
+                self.write_register(Registers::VDCMIN, &mut value)$(.$await)?
+            }
+
+            /// write value to RAMPMODE register
+            pub $($async)? fn set_rampmode(&mut self, val: RampMode) -> Result<DataPacket, Error<E>> {
+                let mut value = (val as u32).to_be_bytes();
+                self.write_register(Registers::RAMPMODE, &mut value)$(.$await)?
+            }
+
+            /// read offset register
+            pub $($async)? fn read_offset(&mut self) -> Result<u32, Error<E>> {
+                self.read_register(Registers::OFFSET_READ)$(.$await)?.map(|packet| packet.data)
+            }
+
+            /// read TSTEP register
+            pub $($async)? fn read_tstep(&mut self) -> Result<u32, Error<E>> {
+                self.read_register(Registers::TSTEP)$(.$await)?.map(|packet| packet.data)
+            }
+
+            /// read LOST_STEPS register, the steps skipped in dcStep operation
+            pub $($async)? fn read_lost_steps(&mut self) -> Result<i32, Error<E>> {
+                self.read::<LostSteps>()$(.$await)?.map(|lost_steps| lost_steps.steps())
+            }
+
+            /// read IOIN register
+            pub $($async)? fn read_ioin(&mut self) -> Result<IoIn, Error<E>> {
+                self.read::<IoIn>()$(.$await)?
+            }
+
+            /// check that a TMC5160 answers and read the state of its pins
+            ///
+            /// Fails with [`Error::UnknownVersion`] if VERSION in IOIN is not [`IoIn::VERSION`], a missing chip usually
+            /// reads as 0x00 or 0xFF.
+            pub $($async)? fn identify(&mut self) -> Result<ChipInfo, Error<E>> {
+                let io_in = self.read_ioin()$(.$await)??;
+                if io_in.version() != IoIn::VERSION {
+                    return Err(Error::UnknownVersion(io_in.version()));
+                }
+                Ok(ChipInfo {
+                    version: io_in.version(),
+                    interface: self.bus.interface(),
+                    sd_mode: io_in.sd_mode(),
+                    drv_enn: io_in.drv_enn(),
+                    refl: io_in.refl_step(),
+                    refr: io_in.refr_dir(),
+                    enc_a: io_in.enca_dcen_cfg5(),
+                    enc_b: io_in.encb_dcen_cfg4(),
+                    enc_n: io_in.enc_n_dco_cfg6(),
+                })
+            }
+
+            /// read OTP_READ register, the reset defaults programmed into the one time programmable memory
+            pub $($async)? fn read_otp(&mut self) -> Result<OtpRead, Error<E>> {
+                self.read::<OtpRead>()$(.$await)?
+            }
+
+            /// permanently program one bit of the OTP memory, e.g. [`OtpRead::TBL_BIT`] of byte 0
+            ///
+            /// A programmed bit can never be cleared again. Bits that are already set are refused with [`Error::OtpBitSet`].
+            /// After 10 ms the bit is read back, [`Error::OtpVerify`] is returned if it is not set. Only byte 0 exists on
+            /// the TMC5160.
+            pub $($async)? fn program_otp_bit<D>(&mut self, byte: u8, bit: u8, _confirm: OtpConfirm, delay: &mut D) -> Result<OtpRead, Error<E>>
+                where
+                    D: DelayNs,
+            {
+                if byte != 0 || bit > 7 {
+                    return Err(Error::OutOfRange);
+                }
+                let mask = 1 << bit;
+                if self.read_otp()$(.$await)??.to_u32() & mask != 0 {
+                    return Err(Error::OtpBitSet);
+                }
+                self.write(&OtpProg::new().with_otpbit(bit).with_otpbyte(byte).with_otpmagic(OtpProg::MAGIC))$(.$await)??;
+                delay.delay_ms(10)$(.$await)?;
+                let otp = self.read_otp()$(.$await)??;
+                if otp.to_u32() & mask == 0 {
+                    return Err(Error::OtpVerify);
+                }
+                Ok(otp)
+            }
+
+            /// read PWM_AUTO register
+            pub $($async)? fn read_pwm_auto(&mut self) -> Result<u32, Error<E>> {
+                self.read_register(Registers::PWM_AUTO)$(.$await)?.map(|packet| packet.data)
+            }
+
+            /// read DRV_STATUS register
+            pub $($async)? fn read_drv_status(&mut self) -> Result<DrvStatus, Error<E>> {
+                self.read::<DrvStatus>()$(.$await)?
+            }
+
+            /// read GSTAT register
+            pub $($async)? fn read_gstat(&mut self) -> Result<GStat, Error<E>> {
+                self.read::<GStat>()$(.$await)?
+            }
+
+            /// read GCONF register
+            pub $($async)? fn read_gconf(&mut self) -> Result<GConf, Error<E>> {
+                self.read::<GConf>()$(.$await)?
+            }
+
+            /// read RAMP_STAT register
+            pub $($async)? fn read_ramp_status(&mut self) -> Result<RampStat, Error<E>> {
+                self.read::<RampStat>()$(.$await)?
+            }
+
+            /// read ENC_STATUS register
+            pub $($async)? fn read_enc_status(&mut self) -> Result<EncStatus, Error<E>> {
+                self.read::<EncStatus>()$(.$await)?
+            }
+
+            /// set the position to 0 / home
+            pub $($async)? fn set_home(&mut self) -> Result<DataPacket, Error<E>> {
+                self.check_not_direct_mode()?;
+                let mut val = 0_u32.to_be_bytes();
+                self.write_register(Registers::XACTUAL, &mut val)$(.$await)??;
+                let packet = self.write_register(Registers::XTARGET, &mut val)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// stop the motor now
+            pub $($async)? fn stop(&mut self) -> Result<DataPacket, Error<E>> {
+                self.check_not_direct_mode()?;
+                self.disable()?;
+                let mut val = 0_u32.to_be_bytes();
+                self.write_register(Registers::VSTART, &mut val)$(.$await)??;
+                self.write_register(Registers::VMAX, &mut val)$(.$await)??;
+                // TODO: check how we can restart the movement afterwards
+                let mut position = self.read_register(Registers::XACTUAL)$(.$await)??.data.to_be_bytes();
+                let packet = self.write_register(Registers::XTARGET, &mut position)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// check if the motor is moving
+            pub $($async)? fn is_moving(&mut self) -> Result<bool, Error<E>> {
+                self.read_drv_status()$(.$await)?.map(|packet| !packet.standstill())
+            }
+
+            /// check if the motor has reached the target position
+            pub $($async)? fn position_is_reached(&mut self) -> Result<bool, Error<E>> {
+                self.read_ramp_status()$(.$await)?.map(|packet| packet.position_reached())
+            }
+
+            /// check if the motor has reached the constant velocity
+            pub $($async)? fn velocity_is_reached(&mut self) -> Result<bool, Error<E>> {
+                self.read_ramp_status()$(.$await)?.map(|packet| packet.velocity_reached())
+            }
+
+            /// check if motor is at right limit
+            pub $($async)? fn is_at_limit_r(&mut self) -> Result<bool, Error<E>> {
+                self.read_ramp_status()$(.$await)?.map(|packet| packet.status_stop_r())
+            }
+
+            /// check if motor is at left limit
+            pub $($async)? fn is_at_limit_l(&mut self) -> Result<bool, Error<E>> {
+                self.read_ramp_status()$(.$await)?.map(|packet| packet.status_stop_l())
+            }
+
+            /// set the max velocity (VMAX), `f32` is full steps per second
+            pub $($async)? fn set_velocity<V: Velocity>(&mut self, velocity: V) -> Result<DataPacket, Error<E>> {
+                let velocity = velocity.to_microsteps_per_sec(&self.mechanics).ok_or(Error::UnitConversion)?;
+                self.v_max = velocity / self._step_count;
+                let v_max = conversion::speed_from_hz(self._clock as f32, 1.0, velocity);
+                let mut val = v_max.to_be_bytes();
+                let packet = self.write_register(Registers::VMAX, &mut val)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// set the max velocity (VMAX)
+            pub $($async)? fn set_velocity_raw(&mut self, velocity: u32) -> Result<DataPacket, Error<E>> {
+                self.v_max = conversion::speed_to_hz(self._clock as f32, self._step_count, velocity);
+                let mut val = velocity.to_be_bytes();
+                let packet = self.write_register(Registers::VMAX, &mut val)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// set the max acceleration (AMAX, DMAX, A1, D1), `f32` is full steps per second squared
+            pub $($async)? fn set_acceleration<A: Acceleration>(&mut self, acceleration: A) -> Result<DataPacket, Error<E>> {
+                let acceleration = acceleration.to_microsteps_per_sec2(&self.mechanics).ok_or(Error::UnitConversion)?;
+                let a_max = conversion::accel_from_hz(self._clock as f32, 1.0, acceleration);
+                let mut val = a_max.to_be_bytes();
+                self.write_register(Registers::AMAX, &mut val)$(.$await)??;
+                self.write_register(Registers::DMAX, &mut val)$(.$await)??;
+                self.write_register(Registers::A1, &mut val)$(.$await)??;
+                let packet = self.write_register(Registers::D1, &mut val)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// write a ramp profile to VSTART, A1, V1, AMAX, VMAX, DMAX, D1 and VSTOP
+            ///
+            /// Nothing is written if the profile does not fit the registers or violates VSTOP > VSTART or D1 != 0.
+            pub $($async)? fn set_ramp_profile<V, A>(&mut self, profile: &RampProfile<V, A>) -> Result<DataPacket, Error<E>>
+                where
+                    V: Velocity + Copy,
+                    A: Acceleration + Copy,
+            {
+                let ramp = profile.to_registers(self._clock as f32, &self.mechanics)?;
+                let registers = [
+                    (Registers::VSTART, ramp.v_start),
+                    (Registers::A1, ramp.a1),
+                    (Registers::V1, ramp.v1),
+                    (Registers::AMAX, ramp.a_max),
+                    (Registers::VMAX, ramp.v_max),
+                    (Registers::DMAX, ramp.d_max),
+                    (Registers::D1, ramp.d1),
+                ];
+                for (reg, val) in registers {
+                    self.write_register(reg, &mut val.to_be_bytes())$(.$await)??;
+                }
+                let packet = self.write_register(Registers::VSTOP, &mut ramp.v_stop.to_be_bytes())$(.$await)??;
+                self.v_max = conversion::speed_to_hz(self._clock as f32, self._step_count, ramp.v_max);
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// set the max velocity (VMAX) in microsteps per second with the fixed-point conversion
+            ///
+            /// `v_max` is not updated, that would take a float conversion.
+            #[cfg(feature = "fixed-point")]
+            pub $($async)? fn set_velocity_fixed(&mut self, velocity: u32) -> Result<DataPacket, Error<E>> {
+                let mut val = crate::fixed::vmax_from_hz(self._clock, velocity).to_be_bytes();
+                let packet = self.write_register(Registers::VMAX, &mut val)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// set the max acceleration (AMAX, DMAX, A1, D1) in microsteps per second squared with the fixed-point conversion
+            #[cfg(feature = "fixed-point")]
+            pub $($async)? fn set_acceleration_fixed(&mut self, acceleration: u32) -> Result<DataPacket, Error<E>> {
+                let a_max = crate::fixed::amax_from_hz_per_s(self._clock, acceleration);
+                let mut val = a_max.to_be_bytes();
+                self.write_register(Registers::AMAX, &mut val)$(.$await)??;
+                self.write_register(Registers::DMAX, &mut val)$(.$await)??;
+                self.write_register(Registers::A1, &mut val)$(.$await)??;
+                let packet = self.write_register(Registers::D1, &mut val)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// move to a target position in microsteps (XTARGET)
+            pub $($async)? fn move_to_microsteps(&mut self, target: Microsteps) -> Result<DataPacket, Error<E>> {
+                self.check_not_direct_mode()?;
+                self.enable()?;
+                let mut val = target.0.to_be_bytes();
+                let packet = self.write_register(Registers::XTARGET, &mut val)$(.$await)??;
+                self.status = packet.status;
+                Ok(packet)
+            }
+
+            /// get the latched position in microsteps (XLATCH)
+            pub $($async)? fn get_latched_microsteps(&mut self) -> Result<Microsteps, Error<E>> {
+                self.read_register(Registers::XLATCH)$(.$await)?.map(|val| Microsteps(val.data as i32))
+            }
+
+            /// get the current position in microsteps (XACTUAL)
+            pub $($async)? fn get_position_microsteps(&mut self) -> Result<Microsteps, Error<E>> {
+                self.read_register(Registers::XACTUAL)$(.$await)?.map(|val| Microsteps(val.data as i32))
+            }
+
+            /// set the current position in microsteps (XACTUAL)
+            pub $($async)? fn set_position_microsteps(&mut self, position: Microsteps) -> Result<DataPacket, Error<E>> {
+                let mut val = position.0.to_be_bytes();
+                self.write_register(Registers::XACTUAL, &mut val)$(.$await)?
+            }
+
+            /// get the current target position in microsteps (XTARGET)
+            pub $($async)? fn get_target_microsteps(&mut self) -> Result<Microsteps, Error<E>> {
+                self.check_not_direct_mode()?;
+                self.read_register(Registers::XTARGET)$(.$await)?.map(|val| Microsteps(val.data as i32))
+            }
+
+            /// get the encoder position in microsteps (X_ENC)
+            pub $($async)? fn get_encoder_microsteps(&mut self) -> Result<Microsteps, Error<E>> {
+                self.read_register(Registers::X_ENC)$(.$await)?.map(|val| Microsteps(val.data as i32))
+            }
+
+            /// get the current signed velocity (VACTUAL)
+            pub $($async)? fn get_velocity_raw(&mut self) -> Result<VelocityRaw, Error<E>> {
+                self.read_register(Registers::VACTUAL)$(.$await)?.map(|val| VelocityRaw::from_vactual(val.data))
+            }
+
+            /// move to a specific location, `f32` is full steps
+            pub $($async)? fn move_to<P: Position>(&mut self, target: P) -> Result<DataPacket, Error<E>> {
+                let target = target.to_microsteps(&self.mechanics)?;
+                self.move_to_microsteps(target)$(.$await)?
+            }
+
+            /// get the latched position
+            pub $($async)? fn get_latched_position(&mut self) -> Result<f32, Error<E>> {
+                let position = self.get_latched_microsteps()$(.$await)??;
+                Ok(conversion::position_from_raw(self._step_count, position))
+            }
+
+            /// get the current position
+            pub $($async)? fn get_position(&mut self) -> Result<f32, Error<E>> {
+                let position = self.get_position_microsteps()$(.$await)??;
+                Ok(conversion::position_from_raw(self._step_count, position))
+            }
+
+            /// get the current position in a unit of [`crate::units`]
+            pub $($async)? fn get_position_as<P: Position>(&mut self) -> Result<P, Error<E>> {
+                let position = self.get_position_microsteps()$(.$await)??;
+                P::from_microsteps(position, &self.mechanics)
+            }
+
+            /// set the current position, `i32` and `f32` are full steps
+            pub $($async)? fn set_position<P: Position>(&mut self, position: P) -> Result<DataPacket, Error<E>> {
+                let position = position.to_microsteps(&self.mechanics)?;
+                self.set_position_microsteps(position)$(.$await)?
+            }
+
+            /// get the current velocity in full steps per second
+            pub $($async)? fn get_velocity(&mut self) -> Result<f32, Error<E>> {
+                self.get_velocity_as::<f32>()$(.$await)?
+            }
+
+            /// get the current velocity in a unit of [`crate::units`]
+            pub $($async)? fn get_velocity_as<V: Velocity>(&mut self) -> Result<V, Error<E>> {
+                let velocity = self.get_velocity_raw()$(.$await)??;
+                let velocity = conversion::speed_to_hz(self._clock as f32, 1.0, velocity.0.unsigned_abs()) * velocity.0.signum() as f32;
+                V::from_microsteps_per_sec(velocity, &self.mechanics).ok_or(Error::UnitConversion)
+            }
+
+            /// get the set maximum velocity (VMAX)
+            pub fn get_velocity_max(&mut self) -> f32 {
+                self.v_max
+            }
+
+            /// get the current target position (XTARGET)
+            pub $($async)? fn get_target(&mut self) -> Result<f32, Error<E>> {
+                let target = self.get_target_microsteps()$(.$await)??;
+                Ok(conversion::position_from_raw(self._step_count, target))
+            }
+
+            /// get the current target position (XTARGET) in a unit of [`crate::units`]
+            pub $($async)? fn get_target_as<P: Position>(&mut self) -> Result<P, Error<E>> {
+                let target = self.get_target_microsteps()$(.$await)??;
+                P::from_microsteps(target, &self.mechanics)
+            }
+
+            /// get the current encoder position (X_ENC)
+            pub $($async)? fn get_encoder_position(&mut self) -> Result<f32, Error<E>> {
+                let position = self.get_encoder_microsteps()$(.$await)??;
+                Ok(conversion::position_from_raw(self._step_count, position))
+            }
+
+            /// get the encoder position latched by the last N event (ENC_LATCH)
+            pub $($async)? fn get_encoder_latch(&mut self) -> Result<f32, Error<E>> {
+                self.read_register(Registers::ENC_LATCH)$(.$await)?.map(|val| conversion::position_from_raw(self._step_count, Microsteps(val.data as i32)))
+            }
+
+            /// set the encoder factor (ENC_CONST) for an encoder with `encoder_lines` lines on a motor with `full_steps` per
+            /// revolution, `full_steps` is negative if the encoder counts in the opposite direction
+            ///
+            /// Binary or decimal mode is taken from `enc_mode.enc_sel_decimal`. Fails with [`Error::OutOfRange`] if the factor
+            /// can not be represented.
+            pub $($async)? fn set_encoder_resolution(&mut self, encoder_lines: u32, full_steps: i32) -> Result<DataPacket, Error<E>> {
+                let microsteps = (full_steps as f32 * self._step_count) as i32;
+                let enc_const = EncConst::from_resolution(encoder_lines, microsteps, self.enc_mode.enc_sel_decimal())
+                    .ok_or(Error::OutOfRange)?;
+                self.write(&enc_const)$(.$await)?
+            }
+
+            /// set the deviation between XACTUAL and X_ENC that sets `deviation_warn` (ENC_DEVIATION), `i32` and `f32` are
+            /// full steps, 0 disables the warning
+            pub $($async)? fn set_encoder_deviation<P: Position>(&mut self, deviation: P) -> Result<DataPacket, Error<E>> {
+                let microsteps = deviation.to_microsteps(&self.mechanics)?;
+                if !(0..=0xF_FFFF).contains(&microsteps.0) {
+                    return Err(Error::OutOfRange);
+                }
+                self.write(&EncDeviation::new().with_enc_deviation(microsteps.0 as u32))$(.$await)?
+            }
+
+            /// check the encoder for lost steps (`ENC_STATUS.deviation_warn`, see [`Self::set_encoder_deviation`])
+            ///
+            /// Returns `None` if no steps have been lost. Otherwise the deviation is corrected according to `action`
+            /// and the warning is cleared.
+            pub $($async)? fn check_step_loss(&mut self, action: StepLossAction) -> Result<Option<StepLoss>, Error<E>> {
+                if !self.read_enc_status()$(.$await)??.deviation_warn() {
+                    return Ok(None);
+                }
+                let [x_actual, x_enc, x_target] = self
+                    .read_many([Registers::XACTUAL, Registers::X_ENC, Registers::XTARGET])$(.$await)??
+                    .map(|packet| packet.data as i32);
+                match action {
+                    StepLossAction::Report => {}
+                    StepLossAction::CorrectPosition => {
+                        self.write_register(Registers::XACTUAL, &mut x_enc.to_be_bytes())$(.$await)??;
+                    }
+                    StepLossAction::Retry => {
+                        self.write_register(Registers::XACTUAL, &mut x_enc.to_be_bytes())$(.$await)??;
+                        self.set_rampmode(RampMode::PositioningMode)$(.$await)??;
+                        self.write_register(Registers::XTARGET, &mut x_target.to_be_bytes())$(.$await)??;
+                    }
+                }
+                self.clear_enc_status()$(.$await)??;
+                Ok(Some(StepLoss { deviation: x_enc.wrapping_sub(x_actual), x_actual, x_enc, x_target }))
+            }
+
+            /// set the position that triggers the position compare pulse (X_COMPARE), `i32` and `f32` are full steps
+            pub $($async)? fn set_compare_position<P: Position>(&mut self, position: P) -> Result<DataPacket, Error<E>> {
+                let position = position.to_microsteps(&self.mechanics)?;
+                self.set_x_compare(position.0 as u32)$(.$await)?
+            }
+
+            /// output the position compare pulse on DIAG1, all other DIAG1 sources are switched off
+            ///
+            /// With `push_pull` DIAG1 is an active high push pull output, otherwise an active low open collector output.
+            pub $($async)? fn route_compare_to_diag1(&mut self, push_pull: bool) -> Result<DataPacket, Error<E>> {
+                self.g_conf.set_diag1_stall(false);
+                self.g_conf.set_diag1_index(false);
+                self.g_conf.set_diag1_onstate(false);
+                self.g_conf.set_diag1_steps_skipped(false);
+                self.g_conf.set_diag1_poscomp_pushpull(push_pull);
+                self.update_g_conf()$(.$await)?
+            }
+
+            /// arm the next point of `points` once the motor has passed the armed one, the first call arms the first point
+            ///
+            /// Returns the index of the point that has been passed since the last call. A point is only triggered if it is
+            /// armed before the motor reaches it, poll faster than the motor moves from one point to the next.
+            pub $($async)? fn rearm_compare<P>(&mut self, points: &mut ComparePoints<'_, P>) -> Result<Option<usize>, Error<E>>
+                where
+                    P: Position + Copy,
+            {
+                if points.is_done() {
+                    return Ok(None);
+                }
+                let x_actual = self.read_register(Registers::XACTUAL)$(.$await)??.data as i32;
+                let mut passed = None;
+                let target = points.positions[points.next].to_microsteps(&self.mechanics)?.0;
+                if points.armed_below.is_some() {
+                    if !points.passed(x_actual, target) {
+                        return Ok(None);
+                    }
+                    passed = Some(points.next - 1);
+                }
+                if let Some(&position) = points.positions.get(points.next) {
+                    let target = position.to_microsteps(&self.mechanics)?.0;
+                    self.set_x_compare(target as u32)$(.$await)??;
+                    points.armed_below = Some(x_actual < target);
+                }
+                Ok(passed)
+            }
+
+            /// switch to direct mode, the coil currents are set with [`Self::set_coil_currents`] instead of the ramp generator
+            ///
+            /// Both currents are set to 0 right after direct mode is switched on.
+            pub $($async)? fn enter_direct_mode(&mut self) -> Result<DataPacket, Error<E>> {
+                self.g_conf.set_direct_mode(true);
+                self.update_g_conf()$(.$await)??;
+                self.write(&XDirect::new())$(.$await)?
+            }
+
+            /// switch back to the ramp generator, the currents are set to 0 and XTARGET to XACTUAL so the motor stays put
+            ///
+            /// XTARGET still holds the last XDIRECT value when direct mode is switched off, the ramp generator is held
+            /// until XTARGET is set and RAMPMODE is restored afterwards.
+            pub $($async)? fn leave_direct_mode(&mut self) -> Result<DataPacket, Error<E>> {
+                self.write(&XDirect::new())$(.$await)??;
+                let ramp_mode = self.shadow.get_raw(Registers::RAMPMODE).unwrap_or(RampMode::PositioningMode as u32);
+                self.set_rampmode(RampMode::HoldMode)$(.$await)??;
+                self.g_conf.set_direct_mode(false);
+                self.update_g_conf()$(.$await)??;
+                let mut position = self.read_register(Registers::XACTUAL)$(.$await)??.data.to_be_bytes();
+                self.write_register(Registers::XTARGET, &mut position)$(.$await)??;
+                self.write_register(Registers::RAMPMODE, &mut ramp_mode.to_be_bytes())$(.$await)?
+            }
+
+            /// set the signed coil currents in direct mode (XDIRECT), the currents are in -255..=255 and scaled by IHOLD
+            ///
+            /// Fails with [`Error::OutOfRange`] if a current is out of range and with [`Error::DirectMode`] if direct mode is
+            /// off, the value would be taken as target position then.
+            pub $($async)? fn set_coil_currents(&mut self, coil_a: i16, coil_b: i16) -> Result<DataPacket, Error<E>> {
+                if !self.g_conf.direct_mode() {
+                    return Err(Error::DirectMode);
+                }
+                let x_direct = XDirect::from_currents(coil_a, coil_b).ok_or(Error::OutOfRange)?;
+                self.write(&x_direct)$(.$await)?
+            }
+
+            /// XTARGET holds the coil currents in direct mode
+            fn check_not_direct_mode(&self) -> Result<(), Error<E>> {
+                if self.g_conf.direct_mode() {
+                    return Err(Error::DirectMode);
+                }
+                Ok(())
+            }
+
+            /// enable dcStep above `velocity` (VDCMIN), sets `vhighfs` and `vhighchm` in CHOPCONF, `f32` is full steps per
+            /// second
+            ///
+            /// dcStep runs the motor in fullstep mode and reduces the velocity under load instead of stalling. Set the
+            /// commutation timing with [`Self::set_dc_step_timing`] first.
+            pub $($async)? fn enable_dc_step<V: Velocity>(&mut self, velocity: V) -> Result<DataPacket, Error<E>> {
+                let velocity = velocity.to_microsteps_per_sec(&self.mechanics).ok_or(Error::UnitConversion)?;
+                self.chop_conf.set_vhighfs(true);
+                self.chop_conf.set_vhighchm(true);
+                self.update_chop_conf()$(.$await)??;
+                self.set_vdcmin(conversion::speed_from_hz(self._clock as f32, 1.0, velocity))$(.$await)?
+            }
+
+            /// disable dcStep (VDCMIN = 0)
+            pub $($async)? fn disable_dc_step(&mut self) -> Result<DataPacket, Error<E>> {
+                self.set_vdcmin(0)$(.$await)?
+            }
+
+            /// set the dcStep timing (DCCTRL) in seconds
+            ///
+            /// `on_time` is the upper PWM on time limit for commutation (DC_TIME / fCLK), set it slightly above the blank
+            /// time. `stall_time` is the max. PWM on time for step loss detection (DC_SG · 16 / fCLK), set it slightly above
+            /// `on_time` / 16 or to 0 to disable it. Fails with [`Error::OutOfRange`] if a time does not fit the register.
+            pub $($async)? fn set_dc_step_timing(&mut self, on_time: f32, stall_time: f32) -> Result<DataPacket, Error<E>> {
+                let dc_time = conversion::ceil(on_time * self._clock as f32);
+                let dc_sg = conversion::ceil(stall_time * self._clock as f32 / 16.0);
+                if !(0.0..=1023.0).contains(&dc_time) || !(0.0..=255.0).contains(&dc_sg) {
+                    return Err(Error::OutOfRange);
+                }
+                self.write(&DcCtrl::new().with_dc_time(dc_time as u16).with_dc_sg(dc_sg as u8))$(.$await)?
+            }
+
+            /// measure the encoder factor with a move by `distance` and write the best ENC_CONST, `i32` and `f32` are full
+            /// steps
+            ///
+            /// The ramp has to be set up in positioning mode, this polls every millisecond until the position is reached and
+            /// fails with [`Error::Timeout`] after `timeout_ms`, the motor is not stopped then. The binary and the decimal
+            /// representation are compared, the better one is written together with `enc_mode.enc_sel_decimal` and X_ENC is
+            /// set to XACTUAL. Fails with [`Error::NoEncoderSignal`] if the encoder did not count.
+            pub $($async)? fn calibrate_encoder<P, D>(
+                &mut self,
+                distance: P,
+                delay: &mut D,
+                timeout_ms: u32,
+            ) -> Result<EncoderCalibration, Error<E>>
+                where
+                    P: Position,
+                    D: DelayNs,
+            {
+                let distance = distance.to_microsteps(&self.mechanics)?;
+                // a factor of 1.0 is the same in binary and decimal mode, X_ENC counts the encoder counts
+                self.write(&EncConst::from_u32(1 << 16))$(.$await)??;
+                let [x_start, enc_start] = self
+                    .read_many([Registers::XACTUAL, Registers::X_ENC])$(.$await)??
+                    .map(|packet| packet.data as i32);
+                self.move_to_microsteps(Microsteps(x_start.wrapping_add(distance.0)))$(.$await)??;
+                let mut waited = 0;
+                while !self.position_is_reached()$(.$await)?? {
+                    if waited >= timeout_ms {
+                        return Err(Error::Timeout);
+                    }
+                    delay.delay_ms(1)$(.$await)?;
+                    waited += 1;
+                }
+                let [x_end, enc_end] = self
+                    .read_many([Registers::XACTUAL, Registers::X_ENC])$(.$await)??
+                    .map(|packet| packet.data as i32);
+
+                let counts = enc_end.wrapping_sub(enc_start);
+                if counts == 0 {
+                    return Err(Error::NoEncoderSignal);
+                }
+                let calibration = EncoderCalibration::new(x_end.wrapping_sub(x_start), counts).ok_or(Error::OutOfRange)?;
+                self.enc_mode.set_enc_sel_decimal(calibration.decimal);
+                self.update_enc_mode()$(.$await)??;
+                self.write(&calibration.enc_const)$(.$await)??;
+                self.write_register(Registers::X_ENC, &mut x_end.to_be_bytes())$(.$await)??;
+                Ok(calibration)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::{Access, Pin, Recorder};
+    use crate::registers::*;
+    use crate::units::Mechanics;
+    use crate::{Error, ResetPolicy, Tmc5160};
+
+    fn driver(bus: Recorder) -> Tmc5160<Recorder, Pin> {
+        Tmc5160::with_transport(bus)
+    }
+
+    #[test]
+    fn leave_direct_mode_holds_the_ramp_generator() {
+        let mut driver = driver(Recorder::new());
+        driver.set_rampmode(RampMode::VelocityModePos).unwrap();
+        driver.enter_direct_mode().unwrap();
+        driver.set_coil_currents(100, -50).unwrap();
+        driver.bus.set_reg(Registers::XACTUAL, 5000);
+        driver.bus.log.clear();
+
+        driver.leave_direct_mode().unwrap();
+        let x_target = Registers::XTARGET as u8;
+        let ramp_mode = Registers::RAMPMODE as u8;
+        assert_eq!(driver.bus.log, [
+            Access::Write(x_target, 0),
+            Access::Write(ramp_mode, RampMode::HoldMode as u32),
+            Access::Write(Registers::GCONF as u8, driver.g_conf.to_u32()),
+            Access::Read(Registers::XACTUAL as u8),
+            Access::Write(x_target, 5000),
+            Access::Write(ramp_mode, RampMode::VelocityModePos as u32),
+        ]);
+        assert!(!driver.g_conf.direct_mode());
+    }
+
+    #[test]
+    fn uart_polls_gstat_only_when_resets_are_handled() {
+        let vmax = Registers::VMAX as u8;
+        let drv_status = Registers::DRV_STATUS as u8;
+
+        let mut ignoring = driver(Recorder::uart()).reset_poll(1);
+        ignoring.set_vmax(1000).unwrap();
+        ignoring.read_drv_status().unwrap();
+        assert_eq!(ignoring.bus.log, [Access::Write(vmax, 1000), Access::Read(drv_status)]);
+
+        let mut reporting = driver(Recorder::uart()).reset_policy(ResetPolicy::Report);
+        reporting.set_vmax(1000).unwrap();
+        assert_eq!(reporting.bus.log, [Access::Write(vmax, 1000)]);
+
+        let mut polling = driver(Recorder::uart()).reset_policy(ResetPolicy::Report).reset_poll(2);
+        polling.set_vmax(1000).unwrap();
+        polling.read_drv_status().unwrap();
+        let gstat = Registers::GSTAT as u8;
+        assert_eq!(polling.bus.log, [Access::Write(vmax, 1000), Access::Read(gstat), Access::Read(drv_status)]);
+    }
+
+    #[test]
+    fn report_policy_fails_until_recovered() {
+        let mut driver = driver(Recorder::new()).reset_policy(ResetPolicy::Report);
+        driver.chop_conf.set_toff(5);
+        driver.update_chop_conf().unwrap();
+        driver.bus.reset();
+
+        assert!(matches!(driver.read_drv_status(), Err(Error::Reset)));
+        assert!(matches!(driver.set_vmax(1000), Err(Error::Reset)));
+        driver.recover().unwrap();
+        assert_eq!(driver.resets, 1);
+        assert_eq!(driver.bus.reg(Registers::GSTAT), 0);
+        assert_eq!(driver.bus.reg(Registers::CHOPCONF), driver.chop_conf.to_u32());
+        driver.read_drv_status().unwrap();
+    }
+
+    #[test]
+    fn restore_policy_replays_the_shadow() {
+        for bus in [Recorder::new(), Recorder::uart()] {
+            let mut driver = driver(bus).reset_policy(ResetPolicy::Restore).reset_poll(1);
+            driver.chop_conf.set_toff(5);
+            driver.ihold_irun.set_i_run(31);
+            driver.commit().unwrap();
+            driver.bus.reset();
+
+            driver.read_drv_status().unwrap();
+            assert_eq!(driver.resets, 1);
+            assert_eq!(driver.bus.reg(Registers::GSTAT), 0);
+            assert_eq!(driver.bus.reg(Registers::CHOPCONF), driver.chop_conf.to_u32());
+            assert_eq!(driver.bus.reg(Registers::IHOLD_IRUN), driver.ihold_irun.to_u32());
+        }
+    }
+
+    #[test]
+    fn commit_writes_changed_registers_in_order() {
+        let mut driver = driver(Recorder::new());
+        driver.chop_conf.set_toff(5);
+        assert!(driver.stage(&IHoldIRun::new().with_i_hold(8).with_i_run(31)));
+        driver.g_conf.set_en_pwm_mode(true);
+        assert!(!driver.stage_raw(Registers::XACTUAL, 100));
+
+        assert_eq!(driver.commit().unwrap(), 3);
+        assert_eq!(driver.bus.writes(), [
+            (Registers::GCONF as u8, driver.g_conf.to_u32()),
+            (Registers::IHOLD_IRUN as u8, driver.ihold_irun.to_u32()),
+            (Registers::CHOPCONF as u8, driver.chop_conf.to_u32()),
+        ]);
+        assert_eq!(driver.ihold_irun.i_run(), 31);
+        assert!(!driver.shadow().has_dirty());
+
+        driver.bus.log.clear();
+        driver.stage(&IHoldIRun::new().with_i_hold(8).with_i_run(31));
+        assert_eq!(driver.commit().unwrap(), 0);
+        assert!(driver.bus.log.is_empty());
+    }
+
+    #[test]
+    fn resync_writes_the_known_configuration() {
+        let mut driver = driver(Recorder::new());
+        driver.chop_conf.set_toff(5);
+        driver.update_chop_conf().unwrap();
+        driver.set_vmax(1000).unwrap();
+        driver.bus.reset();
+        driver.bus.log.clear();
+
+        assert_eq!(driver.resync().unwrap(), 2);
+        assert_eq!(driver.bus.writes(), [
+            (Registers::CHOPCONF as u8, driver.chop_conf.to_u32()),
+            (Registers::VMAX as u8, 1000),
+        ]);
+    }
+
+    #[test]
+    fn mechanics_select_the_resolution() {
+        let mut driver = driver(Recorder::new()).mechanics(Mechanics::new(200, 16));
+        assert_eq!(driver.chop_conf.resolution(), Some(MicrostepResolution::M16));
+        driver.bus.set_reg(Registers::XACTUAL, 1000);
+        driver.update_chop_conf().unwrap();
+        assert_eq!(driver.bus.writes(), [(Registers::CHOPCONF as u8, driver.chop_conf.to_u32())]);
+        assert_eq!(driver.bus.reg(Registers::XACTUAL), 1000);
+        assert_eq!(driver.get_mechanics().microsteps, 16);
+    }
+
+    #[test]
+    #[should_panic]
+    fn step_count_refuses_other_resolutions() {
+        driver(Recorder::new()).step_count(200.0);
+    }
+
+    #[test]
+    fn resolution_change_rescales_positions_and_encoder() {
+        let mut driver = driver(Recorder::new());
+        driver.set_x_compare(1600).unwrap();
+        driver.write(&EncConst::from_ratio(256, 1, false).unwrap()).unwrap();
+        driver.write(&EncDeviation::new().with_enc_deviation(512)).unwrap();
+        driver.bus.set_reg(Registers::XACTUAL, 2560);
+        driver.bus.set_reg(Registers::XTARGET, -2560_i32 as u32);
+        driver.bus.set_reg(Registers::X_ENC, 256);
+        driver.bus.log.clear();
+
+        driver.chop_conf.set_resolution(MicrostepResolution::M16);
+        driver.update_chop_conf().unwrap();
+        let ramp_mode = Registers::RAMPMODE as u8;
+        assert_eq!(driver.bus.writes(), [
+            (ramp_mode, RampMode::HoldMode as u32),
+            (Registers::XACTUAL as u8, 160),
+            (Registers::XTARGET as u8, -160_i32 as u32),
+            (Registers::X_ENC as u8, 16),
+            (ramp_mode, RampMode::PositioningMode as u32),
+            (Registers::X_COMPARE as u8, 100),
+            (Registers::ENC_CONST as u8, 16 << 16),
+            (Registers::ENC_DEVIATION as u8, 32),
+            (Registers::CHOPCONF as u8, driver.chop_conf.to_u32()),
+        ]);
+        assert_eq!(driver.shadow().get_raw(Registers::X_COMPARE), Some(100));
+        assert_eq!(driver.get_mechanics().microsteps, 16);
+    }
+
+    #[test]
+    fn resolution_change_refuses_an_encoder_deviation_out_of_range() {
+        let mut driver = driver(Recorder::new()).mechanics(Mechanics::new(200, 16));
+        driver.write(&EncDeviation::new().with_enc_deviation(0x1_0000)).unwrap();
+        driver.bus.log.clear();
+
+        driver.chop_conf.set_resolution(MicrostepResolution::M256);
+        assert!(matches!(driver.update_chop_conf(), Err(Error::OutOfRange)));
+        assert!(driver.bus.log.is_empty());
+        assert_eq!(driver.get_mechanics().microsteps, 16);
+    }
+}
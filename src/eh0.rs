@@ -0,0 +1,197 @@
+//! Transport for HALs that only implement [`embedded-hal`] 0.2
+//!
+//! Enabled with the `eh0` cargo feature. Chip select is toggled by the transport itself, an EN pin is wrapped into a
+//! [`Pin`].
+//!
+//! ```ignore
+//! let mut stepper_driver = eh0::Tmc5160::new(spi, nss).attach_en(eh0::Pin(en));
+//! ```
+//!
+//! [`embedded-hal`]: https://docs.rs/embedded-hal/0.2
+//!
+
+use core::result::Result;
+
+use embedded_hal::digital::{self, ErrorKind};
+use embedded_hal_0_2::{
+    blocking::spi::{Transfer, Write},
+    digital::v2::OutputPin,
+    spi::{Mode, Phase, Polarity},
+};
+
+use crate::registers::SpiStatus;
+use crate::transport::{read_pipelined, Transport};
+use crate::{DataPacket, Error};
+
+/// SPI mode
+pub const MODE: Mode = Mode {
+    phase: Phase::CaptureOnSecondTransition,
+    polarity: Polarity::IdleHigh,
+};
+
+/// TMC5160 driver on an embedded-hal 0.2 SPI bus with an embedded-hal 0.2 EN pin
+pub type Tmc5160<SPI, CS, EN> = crate::Tmc5160<SpiTransport<SPI, CS>, Pin<EN>>;
+
+impl<SPI, CS, EN, E> Tmc5160<SPI, CS, EN>
+    where
+        SPI: Transfer<u8, Error=E> + Write<u8, Error=E>,
+        CS: OutputPin,
+        EN: OutputPin,
+{
+    /// Create a new driver from a SPI peripheral and a NCS pin
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        crate::Tmc5160::with_transport(SpiTransport::new(spi, cs))
+    }
+}
+
+/// embedded-hal 1.0 output pin on top of an embedded-hal 0.2 pin, e.g. for the EN pin
+pub struct Pin<P>(pub P);
+
+impl<P: OutputPin> digital::ErrorType for Pin<P> {
+    type Error = ErrorKind;
+}
+
+impl<P: OutputPin> digital::OutputPin for Pin<P> {
+    fn set_low(&mut self) -> Result<(), ErrorKind> {
+        self.0.set_low().map_err(|_| ErrorKind::Other)
+    }
+
+    fn set_high(&mut self) -> Result<(), ErrorKind> {
+        self.0.set_high().map_err(|_| ErrorKind::Other)
+    }
+}
+
+/// SPI transport from a SPI bus and a NCS pin
+pub struct SpiTransport<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI, CS, E> SpiTransport<SPI, CS>
+    where
+        SPI: Transfer<u8, Error=E> + Write<u8, Error=E>,
+        CS: OutputPin,
+{
+    /// Create a new transport from a SPI peripheral and a NCS pin
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        SpiTransport { spi, cs }
+    }
+
+    /// release the SPI peripheral and the NCS pin
+    pub fn release(self) -> (SPI, CS) {
+        (self.spi, self.cs)
+    }
+
+    fn read_io(&mut self, addr: u8) -> Result<DataPacket, Error<E>> {
+        self.cs.set_low().ok();
+
+        let mut buffer = [addr, 0, 0, 0, 0];
+
+        let response = self.spi.transfer(&mut buffer).map_err(Error::Spi)?;
+
+        self.cs.set_high().ok();
+
+        Ok(DataPacket {
+            status: SpiStatus::from_bytes([response[0]]),
+            data: u32::from_be_bytes([response[1], response[2], response[3], response[4]]),
+            debug: [response[0], response[1], response[2], response[3], response[4]],
+        })
+    }
+
+    /// read a specified register according to the old implementation
+    pub fn old_read_register(&mut self, register: u8, buffer: &mut [u8; 5]) {
+        let mut read_cmd = [register, 0x00, 0x00, 0x00, 0x00];
+
+        self.cs.set_low().ok();
+        //usb_println(arrform!(64,"write buffer {:?}",read_cmd).as_str());
+        match self.spi.transfer(&mut read_cmd) {
+            Ok(r) => {
+                buffer[0] = r[0];
+                buffer[1] = r[1];
+                buffer[2] = r[2];
+                buffer[3] = r[3];
+                buffer[4] = r[4];
+                //usb_println(arrform!(64,"read answer {:?}",r).as_str());
+            }
+            Err(_err) => {
+                //usb_println(arrform!(64, "spi failed to read = {:?}", err).as_str());
+            }
+        }
+        self.cs.set_high().ok();
+
+        let mut read_cmd = [register, 0x00, 0x00, 0x00, 0x00];
+
+        self.cs.set_low().ok();
+        //usb_println(arrform!(64,"write buffer {:?}",read_cmd).as_str());
+        match self.spi.transfer(&mut read_cmd) {
+            Ok(r) => {
+                buffer[0] = r[0];
+                buffer[1] = r[1];
+                buffer[2] = r[2];
+                buffer[3] = r[3];
+                buffer[4] = r[4];
+                //usb_println(arrform!(64,"read answer {:?}",r).as_str());
+            }
+            Err(_err) => {
+                //usb_println(arrform!(64, "spi failed to read = {:?}", err).as_str());
+            }
+        }
+        self.cs.set_high().ok();
+    }
+
+    /// write value to a specified register according to the old implementation
+    pub fn old_write_register(&mut self, register: u8, payload: &[u8; 4]) -> u8 {
+        self.cs.set_low().ok();
+        let mut status_byte = 0;
+        let mut buffer: [u8; 5] = [register | 0x80, payload[0], payload[1], payload[2], payload[3]];
+        // usb_println(arrform!(64,"write buffer {:?}",buffer).as_str());
+        match self.spi.transfer(&mut buffer) {
+            Ok(r) => {
+                //usb_println(arrform!(64,"write answer {:?}",r).as_str());
+                status_byte = r[0];
+            }
+            Err(_err) => {
+                //usb_println(arrform!(64, "spi failed to write = {:?}", err).as_str());
+            }
+        }
+        self.cs.set_high().ok();
+        status_byte
+    }
+}
+
+impl<SPI, CS, E> Transport for SpiTransport<SPI, CS>
+    where
+        SPI: Transfer<u8, Error=E> + Write<u8, Error=E>,
+        CS: OutputPin,
+{
+    type Error = E;
+
+    fn read_register(&mut self, addr: u8) -> Result<DataPacket, Error<E>> {
+        // Process cmd to read, return previous (dummy) state
+        let _dummy = self.read_io(addr)?;
+        // Repeat cmd to read, return state
+        self.read_io(addr)
+    }
+
+    fn read_registers<const N: usize>(&mut self, addrs: [u8; N]) -> Result<[DataPacket; N], Error<E>> {
+        read_pipelined(addrs, |addr| self.read_io(addr))
+    }
+
+    fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<E>> {
+        self.cs.set_low().ok();
+
+        let mut buffer = [addr | 0x80, val[0], val[1], val[2], val[3]];
+
+        let debug_val = buffer;
+
+        let response = self.spi.transfer(&mut buffer).map_err(Error::Spi)?;
+
+        self.cs.set_high().ok();
+
+        Ok(DataPacket {
+            status: SpiStatus::from_bytes([response[0]]),
+            data: u32::from_be_bytes([response[1], response[2], response[3], response[4]]),
+            debug: debug_val,
+        })
+    }
+}
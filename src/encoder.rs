@@ -0,0 +1,86 @@
+//! Encoder supervision
+//!
+//! With `ENC_DEVIATION` set, the TMC5160 compares XACTUAL with X_ENC and sets `ENC_STATUS.deviation_warn` when the
+//! difference exceeds the threshold, e.g. because the motor lost steps. [`crate::Tmc5160::check_step_loss`] polls the
+//! flag, reports the deviation and optionally corrects it. [`crate::Tmc5160::calibrate_encoder`] measures the
+//! encoder factor `ENC_CONST` with a calibration move.
+
+use crate::registers::EncConst;
+
+/// reaction of [`crate::Tmc5160::check_step_loss`] on lost steps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepLossAction {
+    /// only report the deviation
+    Report,
+    /// set XACTUAL to the encoder position, in positioning mode the ramp generator continues to XTARGET from there
+    CorrectPosition,
+    /// set XACTUAL to the encoder position and move to the original target again in positioning mode
+    Retry,
+}
+
+/// lost steps detected by the encoder
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepLoss {
+    /// X_ENC - XACTUAL in microsteps
+    pub deviation: i32,
+    /// XACTUAL in microsteps when the loss was detected
+    pub x_actual: i32,
+    /// X_ENC in microsteps when the loss was detected
+    pub x_enc: i32,
+    /// XTARGET in microsteps when the loss was detected
+    pub x_target: i32,
+}
+
+/// result of [`crate::Tmc5160::calibrate_encoder`]
+#[derive(Clone, Copy)]
+pub struct EncoderCalibration {
+    /// ENC_CONST that has been written
+    pub enc_const: EncConst,
+    /// `enc_const` is in decimal mode, `EncMode::enc_sel_decimal` has been set accordingly
+    pub decimal: bool,
+    /// change of XACTUAL during the calibration move
+    pub microsteps: i32,
+    /// encoder counts during the calibration move
+    pub counts: i32,
+    /// error of X_ENC over the calibration move in microsteps that remains with `enc_const`
+    pub residual: f32,
+    /// the encoder counts against the motor direction
+    ///
+    /// The negative `enc_const` compensates this. Alternatively swap the A and B signals or toggle `GConf::shaft`,
+    /// which reverses the motor, and calibrate again. `EncMode::pol_a` / `pol_b` only select the signal levels of a
+    /// N event and do not change the counting direction.
+    pub reversed: bool,
+}
+
+impl EncoderCalibration {
+    /// best ENC_CONST for `counts` encoder counts over `microsteps`, `None` if it can not be represented
+    pub(crate) fn new(microsteps: i32, counts: i32) -> Option<Self> {
+        let residual = |enc_const: EncConst, decimal| enc_const.factor(decimal) * counts as f32 - microsteps as f32;
+        let binary = EncConst::from_ratio(microsteps, counts, false);
+        let decimal = EncConst::from_ratio(microsteps, counts, true);
+        let (enc_const, decimal) = match (binary, decimal) {
+            (Some(binary), Some(decimal)) => {
+                if abs(residual(decimal, true)) < abs(residual(binary, false)) {
+                    (decimal, true)
+                } else {
+                    (binary, false)
+                }
+            }
+            (Some(binary), None) => (binary, false),
+            (None, Some(decimal)) => (decimal, true),
+            (None, None) => return None,
+        };
+        Some(EncoderCalibration {
+            enc_const,
+            decimal,
+            microsteps,
+            counts,
+            residual: residual(enc_const, decimal),
+            reversed: (microsteps < 0) != (counts < 0),
+        })
+    }
+}
+
+fn abs(val: f32) -> f32 {
+    if val < 0.0 { -val } else { val }
+}
@@ -0,0 +1,123 @@
+//! Fixed-point unit conversion for targets without an FPU
+//!
+//! The conversions use the datasheet formulas v = VMAX * fCLK / 2^24 and a = AMAX * fCLK^2 / 2^41 with velocities in
+//! microsteps per second, accelerations in microsteps per second squared and `clock` in Hz. Results are rounded to
+//! the nearest integer, no floating point operations are involved. All intermediate values fit 64 bits, so no 128 bit
+//! division is pulled in.
+
+/// convert a velocity to the VMAX register value
+pub fn vmax_from_hz(clock: u32, velocity: u32) -> u32 {
+    div_round((velocity as u64) << 24, clock as u64)
+}
+
+/// convert a VMAX or VACTUAL register value to a velocity
+pub fn vmax_to_hz(clock: u32, vmax: u32) -> u32 {
+    div_round(vmax as u64 * clock as u64, 1 << 24)
+}
+
+/// convert an acceleration to the AMAX, DMAX, A1 or D1 register value
+pub fn amax_from_hz_per_s(clock: u32, acceleration: u32) -> u32 {
+    // acceleration * 2^41 does not fit 64 bits, divide by the clock between shifting by 2^24 and 2^17
+    let clock = clock as u64;
+    let shifted = (acceleration as u64) << 24;
+    let per_clock = (shifted / clock)
+        .checked_mul(1 << 17)
+        .map(|high| high.saturating_add(((shifted % clock) << 17) / clock))
+        .unwrap_or(u64::MAX);
+    div_round(per_clock, clock)
+}
+
+/// convert an AMAX, DMAX, A1 or D1 register value to an acceleration
+pub fn amax_to_hz_per_s(clock: u32, amax: u32) -> u32 {
+    // AMAX * fCLK^2 does not fit 64 bits, divide by 2^17 before the second multiplication
+    let clock = clock as u64;
+    let per_clock = amax as u64 * clock;
+    let shifted = (per_clock >> 17)
+        .checked_mul(clock)
+        .and_then(|high| high.checked_add(((per_clock & 0x1_FFFF) * clock) >> 17))
+        .unwrap_or(u64::MAX);
+    div_round(shifted, 1 << 24)
+}
+
+/// divide and round to the nearest integer, saturating at `u32::MAX`
+fn div_round(numerator: u64, denominator: u64) -> u32 {
+    let quotient = numerator.saturating_add(denominator / 2) / denominator;
+    u32::try_from(quotient).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::{accel_from_hz, speed_from_hz, speed_to_hz};
+
+    const CLOCKS: [u32; 4] = [4_000_000, 12_000_000, 16_000_000, 18_000_000];
+    const VMAX_MAX: u32 = (1 << 23) - 512;
+    const AMAX_MAX: u32 = (1 << 16) - 1;
+
+    fn assert_within_lsb(fixed: u32, float: f32, what: &str) {
+        assert!((fixed as f64 - float as f64).abs() <= 1.0, "{}: fixed {} float {}", what, fixed, float);
+    }
+
+    #[test]
+    fn velocity_to_hz() {
+        for clock in CLOCKS {
+            for vmax in (0..=VMAX_MAX).step_by(7) {
+                assert_within_lsb(vmax_to_hz(clock, vmax), speed_to_hz(clock as f32, 1.0, vmax), "VMAX to Hz");
+            }
+        }
+    }
+
+    #[test]
+    fn velocity_from_hz() {
+        for clock in CLOCKS {
+            let max = vmax_to_hz(clock, VMAX_MAX);
+            for velocity in (0..=max).step_by(3) {
+                let fixed = vmax_from_hz(clock, velocity);
+                assert!(fixed <= VMAX_MAX + 1);
+                assert_within_lsb(fixed, speed_from_hz(clock as f32, 1.0, velocity as f32) as f32, "Hz to VMAX");
+            }
+        }
+    }
+
+    #[test]
+    fn acceleration_from_hz() {
+        for clock in CLOCKS {
+            let max = amax_to_hz_per_s(clock, AMAX_MAX);
+            for acceleration in (0..=max).step_by((max / 200_000).max(1) as usize) {
+                let fixed = amax_from_hz_per_s(clock, acceleration);
+                assert_within_lsb(fixed, accel_from_hz(clock as f32, 1.0, acceleration as f32) as f32, "Hz/s to AMAX");
+            }
+        }
+    }
+
+    #[test]
+    fn acceleration_to_hz() {
+        for clock in CLOCKS {
+            for amax in 0..=AMAX_MAX {
+                let hz = amax_to_hz_per_s(clock, amax);
+                assert_within_lsb(amax, accel_from_hz(clock as f32, 1.0, hz as f32) as f32, "AMAX to Hz/s");
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for clock in CLOCKS {
+            for vmax in (0..=VMAX_MAX).step_by(4093) {
+                let back = vmax_from_hz(clock, vmax_to_hz(clock, vmax));
+                assert!(back.abs_diff(vmax) <= 1 + (1 << 24) / clock, "VMAX {} came back as {}", vmax, back);
+            }
+            for amax in 0..=AMAX_MAX {
+                let hz = amax_to_hz_per_s(clock, amax);
+                assert_eq!(amax_from_hz_per_s(clock, hz), amax, "AMAX {} at {} Hz", amax, clock);
+            }
+        }
+    }
+
+    #[test]
+    fn saturation() {
+        assert_eq!(vmax_from_hz(1, u32::MAX), u32::MAX);
+        assert_eq!(amax_from_hz_per_s(1, u32::MAX), u32::MAX);
+        assert_eq!(amax_to_hz_per_s(u32::MAX, u32::MAX), u32::MAX);
+    }
+}
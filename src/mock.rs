@@ -0,0 +1,129 @@
+//! Recording transport, pins and delay for the unit tests
+
+extern crate std;
+
+use core::convert::Infallible;
+use std::vec::Vec;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{ErrorType, OutputPin};
+
+use crate::registers::{Registers, SpiStatus};
+use crate::transport::{Interface, Transport};
+use crate::{DataPacket, Error};
+
+/// register access seen by the [`Recorder`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Access {
+    Read(u8),
+    Write(u8, u32),
+}
+
+/// chip model that keeps the register values and records every access
+///
+/// Writes to GSTAT clear the written bits, the reset flag in the status of every SPI reply follows GSTAT.reset.
+pub(crate) struct Recorder {
+    pub(crate) regs: [u32; 128],
+    pub(crate) log: Vec<Access>,
+    pub(crate) interface: Interface,
+}
+
+impl Recorder {
+    pub(crate) fn new() -> Self {
+        Recorder { regs: [0; 128], log: Vec::new(), interface: Interface::Spi }
+    }
+
+    pub(crate) fn uart() -> Self {
+        Recorder { interface: Interface::Uart, ..Self::new() }
+    }
+
+    /// the chip lost its configuration, GSTAT.reset is set
+    pub(crate) fn reset(&mut self) {
+        self.regs = [0; 128];
+        self.regs[Registers::GSTAT as usize] = 1;
+    }
+
+    pub(crate) fn reg(&self, reg: Registers) -> u32 {
+        self.regs[reg as usize]
+    }
+
+    pub(crate) fn set_reg(&mut self, reg: Registers, val: u32) {
+        self.regs[reg as usize] = val;
+    }
+
+    /// register writes in order, without the reads
+    pub(crate) fn writes(&self) -> Vec<(u8, u32)> {
+        self.log
+            .iter()
+            .filter_map(|access| match *access {
+                Access::Write(addr, val) => Some((addr, val)),
+                Access::Read(_) => None,
+            })
+            .collect()
+    }
+
+    fn packet(&self, data: u32) -> DataPacket {
+        let reset = self.interface == Interface::Spi && self.reg(Registers::GSTAT) & 1 != 0;
+        let status = SpiStatus::new().with_reset_flag(reset);
+        DataPacket { status, data, debug: [0; 5] }
+    }
+}
+
+impl Transport for Recorder {
+    type Error = Infallible;
+
+    fn read_register(&mut self, addr: u8) -> Result<DataPacket, Error<Infallible>> {
+        self.log.push(Access::Read(addr));
+        Ok(self.packet(self.regs[addr as usize & 0x7F]))
+    }
+
+    fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<Infallible>> {
+        let val = u32::from_be_bytes(val);
+        self.log.push(Access::Write(addr, val));
+        let packet = self.packet(0);
+        if addr == Registers::GSTAT as u8 {
+            self.regs[addr as usize] &= !val;
+        } else {
+            self.regs[addr as usize & 0x7F] = val;
+        }
+        Ok(packet)
+    }
+
+    fn interface(&self) -> Interface {
+        self.interface
+    }
+}
+
+/// pin that records its levels
+#[derive(Default)]
+pub(crate) struct Pin {
+    pub(crate) levels: Vec<bool>,
+}
+
+impl ErrorType for Pin {
+    type Error = Infallible;
+}
+
+impl OutputPin for Pin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.levels.push(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.levels.push(true);
+        Ok(())
+    }
+}
+
+/// delay that only adds up the requested time
+#[derive(Default)]
+pub(crate) struct Delay {
+    pub(crate) ns: u64,
+}
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.ns += ns as u64;
+    }
+}
@@ -0,0 +1,183 @@
+//! Microstep lookup table
+//!
+//! The TMC5160 generates the coil currents from a quarter wave of 256 entries. The table is stored differentially in
+//! the 256 bits of MSLUT0..7: bit x gives the step from entry x - 1 to entry x, which is `Wx - 1` for a 0 and `Wx`
+//! for a 1. The segment widths `Wx` (0 to 3) are set in MSLUTSEL for up to four segments starting at 0, X1, X2 and
+//! X3, so every step has to be in -1..=3 and all steps in a segment have to fit one width.
+//!
+//! ```ignore
+//! // sine wave with a slightly flattened top
+//! let ms_lut = MsLut::from_fn(|x| sine_with_compensation(x)).ok_or(Error::OutOfRange)?;
+//! stepper_driver.write_ms_lut(&ms_lut)?;
+//! ```
+
+use crate::registers::{MsLutSel, MsLutStart, Register};
+
+/// Microstep table as written to MSLUT0..7, MSLUTSEL and MSLUTSTART
+#[derive(Clone, Copy)]
+pub struct MsLut {
+    /// MSLUT0 to MSLUT7
+    pub words: [u32; 8],
+    /// segment widths and boundaries
+    pub sel: MsLutSel,
+    /// start values of the sine and the cosine wave
+    pub start: MsLutStart,
+}
+
+impl Default for MsLut {
+    /// power on default sine wave
+    fn default() -> Self {
+        MsLut {
+            words: [
+                0xAAAA_B554,
+                0x4A95_54AA,
+                0x2449_2929,
+                0x1010_4222,
+                0xFBFF_FFFF,
+                0xB5BB_777D,
+                0x4929_5556,
+                0x0040_4222,
+            ],
+            sel: MsLutSel::from_u32(0xFFFF_8056),
+            start: MsLutStart::from_u32(0x00F7_0000),
+        }
+    }
+}
+
+impl MsLut {
+    /// encode a quarter wave of 256 current values
+    ///
+    /// Returns `None` if a step between two entries is outside of -1..=3 or more than four segments are needed.
+    pub fn from_table(table: &[u8; 256]) -> Option<Self> {
+        // segment starts and widths, unused segments start at 255 with the width of the last used one
+        let mut starts = [0_u8, 255, 255, 255];
+        let mut widths = [0_u8; 4];
+        let mut segment = 0;
+        // widths that fit all steps of the current segment, bit w is width w
+        let mut fitting = 0b1111_u8;
+        let mut words = [0_u32; 8];
+
+        for x in 1..256 {
+            let step = table[x] as i16 - table[x - 1] as i16;
+            if !(-1..=3).contains(&step) {
+                return None;
+            }
+            // a step is encoded by width `step` (bit 0) or `step + 1` (bit 1)
+            let widths_for_step = (0b11_u8 << (step + 1)) >> 1 & 0b1111;
+            if fitting & widths_for_step == 0 {
+                widths[segment] = fitting.trailing_zeros() as u8;
+                segment += 1;
+                if segment == 4 {
+                    return None;
+                }
+                starts[segment] = x as u8;
+                fitting = 0b1111;
+            }
+            fitting &= widths_for_step;
+        }
+        widths[segment] = fitting.trailing_zeros() as u8;
+        for unused in segment + 1..4 {
+            widths[unused] = widths[segment];
+        }
+
+        // the widths are known now, set the bits of the steps that use the upper value of their segment
+        segment = 0;
+        for x in 1..256 {
+            while segment < 3 && x >= starts[segment + 1] as usize {
+                segment += 1;
+            }
+            let step = table[x] as i16 - table[x - 1] as i16;
+            if step == widths[segment] as i16 {
+                words[x / 32] |= 1 << (x % 32);
+            }
+        }
+
+        Some(MsLut {
+            words,
+            sel: MsLutSel::new()
+                .with_w0(widths[0])
+                .with_w1(widths[1])
+                .with_w2(widths[2])
+                .with_w3(widths[3])
+                .with_x1(starts[1])
+                .with_x2(starts[2])
+                .with_x3(starts[3]),
+            start: MsLutStart::new().with_start_sin(table[0]).with_start_sin90(table[255]),
+        })
+    }
+
+    /// encode a quarter wave given by `f(x)` for the entries x = 0 to 255
+    pub fn from_fn<F>(mut f: F) -> Option<Self>
+        where
+            F: FnMut(u8) -> u8,
+    {
+        Self::from_table(&core::array::from_fn(|x| f(x as u8)))
+    }
+
+    /// decode the quarter wave
+    pub fn table(&self) -> [u8; 256] {
+        let sel = self.sel;
+        let mut table = [0_u8; 256];
+        table[0] = self.start.start_sin();
+        for x in 1..256 {
+            let width = if x < sel.x1() as usize {
+                sel.w0()
+            } else if x < sel.x2() as usize {
+                sel.w1()
+            } else if x < sel.x3() as usize {
+                sel.w2()
+            } else {
+                sel.w3()
+            };
+            let bit = (self.words[x / 32] >> (x % 32) & 1) as u8;
+            table[x] = table[x - 1].wrapping_add(width + bit).wrapping_sub(1);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// table from segments of (entries, step)
+    fn table(segments: &[(usize, i16)]) -> [u8; 256] {
+        let mut table = [0_u8; 256];
+        let mut x = 1;
+        for &(entries, step) in segments {
+            for _ in 0..entries {
+                table[x] = (table[x - 1] as i16 + step) as u8;
+                x += 1;
+            }
+        }
+        assert_eq!(x, 256);
+        table
+    }
+
+    #[test]
+    fn default_table_round_trip() {
+        let default = MsLut::default();
+        let encoded = MsLut::from_table(&default.table()).unwrap();
+        assert_eq!(encoded.table(), default.table());
+        assert_eq!(encoded.start.to_u32(), default.start.to_u32());
+        assert_eq!(default.table()[255], 247);
+    }
+
+    #[test]
+    fn four_segments_with_width_3() {
+        let table = table(&[(40, 3), (60, 1), (100, -1), (55, 2)]);
+        let ms_lut = MsLut::from_table(&table).unwrap();
+        assert_eq!(ms_lut.table(), table);
+        let sel = ms_lut.sel;
+        assert_eq!([sel.w0(), sel.w1(), sel.w2(), sel.w3()], [3, 1, 0, 2]);
+        assert_eq!([sel.x1(), sel.x2(), sel.x3()], [41, 101, 201]);
+    }
+
+    #[test]
+    fn refused_tables() {
+        // step of 4
+        assert!(MsLut::from_table(&table(&[(10, 4), (245, 0)])).is_none());
+        // five segments
+        assert!(MsLut::from_table(&table(&[(20, 3), (20, -1), (20, 3), (20, -1), (175, 1)])).is_none());
+    }
+}
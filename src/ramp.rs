@@ -0,0 +1,163 @@
+//! Six point ramp profiles
+//!
+//! A [`RampProfile`] describes all velocities and accelerations of the ramp generator in physical units. It is
+//! converted with the mechanics of the axis, checked against the datasheet constraints and written to VSTART, A1,
+//! V1, AMAX, VMAX, DMAX, D1 and VSTOP by [`crate::Tmc5160::set_ramp_profile`]:
+//!
+//! ```ignore
+//! let profile = RampProfile::new(
+//!     MmPerSec(0.0), MmPerSec(10.0), MmPerSec(50.0), MmPerSec(0.5),
+//!     MmPerSec2(400.0), MmPerSec2(200.0),
+//! );
+//! stepper_driver.set_ramp_profile(&profile)?;
+//! ```
+
+use crate::conversion;
+use crate::units::{Acceleration, Mechanics, Velocity};
+use crate::Error;
+
+/// velocities and accelerations of the six point ramp in a unit of [`crate::units`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RampProfile<V, A> {
+    /// start velocity (VSTART)
+    pub v_start: V,
+    /// velocity where the acceleration changes from A1 to AMAX (V1), 0 only uses AMAX and DMAX
+    pub v1: V,
+    /// max velocity (VMAX)
+    pub v_max: V,
+    /// stop velocity (VSTOP), has to be above the start velocity
+    pub v_stop: V,
+    /// acceleration between VSTART and V1 (A1)
+    pub a1: A,
+    /// acceleration between V1 and VMAX (AMAX)
+    pub a_max: A,
+    /// deceleration between VMAX and V1 (DMAX)
+    pub d_max: A,
+    /// deceleration between V1 and VSTOP (D1), must not be 0
+    pub d1: A,
+}
+
+impl<V, A> RampProfile<V, A>
+    where
+        V: Velocity + Copy,
+        A: Acceleration + Copy,
+{
+    /// Create a profile where the deceleration mirrors the acceleration (DMAX = AMAX, D1 = A1)
+    pub fn new(v_start: V, v1: V, v_max: V, v_stop: V, a1: A, a_max: A) -> Self {
+        RampProfile { v_start, v1, v_max, v_stop, a1, a_max, d_max: a_max, d1: a1 }
+    }
+
+    /// specify a deceleration that differs from the acceleration
+    pub fn deceleration(mut self, d_max: A, d1: A) -> Self {
+        self.d_max = d_max;
+        self.d1 = d1;
+        self
+    }
+
+    /// convert the profile to register values with the clock and the mechanics of the axis and check them
+    pub fn to_registers<E>(&self, clock: f32, mechanics: &Mechanics) -> Result<RampRegisters, Error<E>> {
+        let velocity = |v: V| {
+            v.to_microsteps_per_sec(mechanics)
+                .map(|v| conversion::speed_from_hz(clock, 1.0, v))
+                .ok_or(Error::UnitConversion)
+        };
+        let acceleration = |a: A| {
+            a.to_microsteps_per_sec2(mechanics)
+                .map(|a| conversion::accel_from_hz(clock, 1.0, a))
+                .ok_or(Error::UnitConversion)
+        };
+        let registers = RampRegisters {
+            v_start: velocity(self.v_start)?,
+            a1: acceleration(self.a1)?,
+            v1: velocity(self.v1)?,
+            a_max: acceleration(self.a_max)?,
+            v_max: velocity(self.v_max)?,
+            d_max: acceleration(self.d_max)?,
+            d1: acceleration(self.d1)?,
+            v_stop: velocity(self.v_stop)?,
+        };
+        registers.check()?;
+        Ok(registers)
+    }
+}
+
+/// register values of a [`RampProfile`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RampRegisters {
+    /// VSTART
+    pub v_start: u32,
+    /// A1
+    pub a1: u32,
+    /// V1
+    pub v1: u32,
+    /// AMAX
+    pub a_max: u32,
+    /// VMAX
+    pub v_max: u32,
+    /// DMAX
+    pub d_max: u32,
+    /// D1
+    pub d1: u32,
+    /// VSTOP
+    pub v_stop: u32,
+}
+
+impl RampRegisters {
+    /// max value of VMAX
+    pub const V_MAX: u32 = (1 << 23) - 512;
+
+    /// check the register ranges ([`Error::OutOfRange`]) and that VSTOP > VSTART and D1 is not 0
+    /// ([`Error::InvalidRamp`])
+    pub fn check<E>(&self) -> Result<(), Error<E>> {
+        let ranges = [
+            (self.v_start, (1 << 18) - 1),
+            (self.a1, (1 << 16) - 1),
+            (self.v1, (1 << 20) - 1),
+            (self.a_max, (1 << 16) - 1),
+            (self.v_max, Self::V_MAX),
+            (self.d_max, (1 << 16) - 1),
+            (self.d1, (1 << 16) - 1),
+            (self.v_stop, (1 << 18) - 1),
+        ];
+        if ranges.iter().any(|&(value, max)| value > max) {
+            return Err(Error::OutOfRange);
+        }
+        if self.v_stop <= self.v_start || self.d1 == 0 {
+            return Err(Error::InvalidRamp);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: RampRegisters =
+        RampRegisters { v_start: 0, a1: 1000, v1: 50_000, a_max: 500, v_max: 200_000, d_max: 700, d1: 1400, v_stop: 10 };
+
+    fn check(registers: RampRegisters) -> Result<(), Error<()>> {
+        registers.check()
+    }
+
+    #[test]
+    fn valid_ramp() {
+        assert!(check(VALID).is_ok());
+        assert!(check(RampRegisters { v_max: RampRegisters::V_MAX, v_stop: (1 << 18) - 1, ..VALID }).is_ok());
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert!(matches!(check(RampRegisters { v_max: RampRegisters::V_MAX + 1, ..VALID }), Err(Error::OutOfRange)));
+        assert!(matches!(check(RampRegisters { a_max: 1 << 16, ..VALID }), Err(Error::OutOfRange)));
+        assert!(matches!(check(RampRegisters { v1: 1 << 20, ..VALID }), Err(Error::OutOfRange)));
+        assert!(matches!(check(RampRegisters { v_stop: 1 << 18, ..VALID }), Err(Error::OutOfRange)));
+    }
+
+    #[test]
+    fn invalid_ramp() {
+        assert!(matches!(check(RampRegisters { v_stop: 0, ..VALID }), Err(Error::InvalidRamp)));
+        assert!(matches!(check(RampRegisters { v_start: 20, ..VALID }), Err(Error::InvalidRamp)));
+        assert!(matches!(check(RampRegisters { d1: 0, ..VALID }), Err(Error::InvalidRamp)));
+    }
+}
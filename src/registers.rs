@@ -9,6 +9,14 @@ use modular_bitfield_to_value::ToValue;
 pub trait Address {
     /// convert register enum to u8 address
     fn addr(self) -> u8;
+
+    /// access mode of the register
+    fn access(self) -> Access
+        where
+            Self: Sized,
+    {
+        Access::RW
+    }
 }
 
 
@@ -142,10 +150,122 @@ pub enum Registers {
     LOST_STEPS = 0x73,
 }
 
+impl Registers {
+    /// access mode of the register as specified in the datasheet
+    pub const fn access(self) -> Access {
+        match self {
+            Registers::IFCNT | Registers::IOIN | Registers::OTP_READ | Registers::OFFSET_READ | Registers::TSTEP
+            | Registers::VACTUAL | Registers::XLATCH | Registers::ENC_LATCH | Registers::MSCNT
+            | Registers::MSCURACT | Registers::DRV_STATUS | Registers::PWM_SCALE | Registers::PWM_AUTO
+            | Registers::LOST_STEPS => Access::R,
+            Registers::SLAVECONF | Registers::X_COMPARE | Registers::OTP_PROG | Registers::SHORT_CONF
+            | Registers::DRV_CONF | Registers::GLOBALSCALER | Registers::IHOLD_IRUN | Registers::TPOWERDOWN
+            | Registers::TPWMTHRS | Registers::TCOOLTHRS | Registers::THIGH | Registers::VSTART | Registers::A1
+            | Registers::V1 | Registers::AMAX | Registers::VMAX | Registers::DMAX | Registers::D1
+            | Registers::VSTOP | Registers::TZEROWAIT | Registers::VDCMIN | Registers::ENC_CONST
+            | Registers::ENC_DEVIATION | Registers::MSLUT_0_7 | Registers::MSLUTSEL | Registers::MSLUTSTART
+            | Registers::COOLCONF | Registers::DCCTRL | Registers::PWMCONF => Access::W,
+            Registers::GCONF | Registers::FACTORY_CONF | Registers::RAMPMODE | Registers::XACTUAL
+            | Registers::XTARGET | Registers::SW_MODE | Registers::ENCMODE | Registers::X_ENC
+            | Registers::CHOPCONF => Access::RW,
+            Registers::RAMP_STAT => Access::RC,
+            Registers::GSTAT | Registers::ENC_STATUS => Access::WC,
+        }
+    }
+}
+
 impl Address for Registers {
     fn addr(self) -> u8 {
         self as u8
     }
+
+    fn access(self) -> Access {
+        Registers::access(self)
+    }
+}
+
+/// Access mode of a register
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+    /// read only
+    R,
+    /// write only
+    W,
+    /// read and write
+    RW,
+    /// read only, reading clears flags
+    RC,
+    /// read and write, writing 1 clears flags
+    WC,
+}
+
+impl Access {
+    /// the register can be read
+    pub fn readable(self) -> bool {
+        self != Access::W
+    }
+
+    /// the register can be written
+    pub fn writable(self) -> bool {
+        matches!(self, Access::W | Access::RW | Access::WC)
+    }
+}
+
+/// Typed register, links a bitfield to its address and access mode
+///
+/// The bitfields store the register LSB first, the 32 bit value is sent MSB first on the bus.
+/// Whether a register can be read or written is encoded by the [`Readable`] and [`Writable`] marker traits.
+pub trait Register: Copy {
+    /// address of the register
+    const ADDR: Registers;
+    /// access mode of the register
+    const ACCESS: Access;
+
+    /// create the register from its 32 bit value
+    fn from_u32(val: u32) -> Self;
+
+    /// 32 bit value of the register
+    fn to_u32(&self) -> u32;
+}
+
+/// Typed register that can be read
+pub trait Readable: Register {}
+
+/// Typed register that can be written
+pub trait Writable: Register {}
+
+macro_rules! register {
+    (@access $reg:ty, R) => {
+        impl Readable for $reg {}
+    };
+    (@access $reg:ty, W) => {
+        impl Writable for $reg {}
+    };
+    (@access $reg:ty, RC) => {
+        impl Readable for $reg {}
+    };
+    (@access $reg:ty, $access:ident) => {
+        impl Readable for $reg {}
+        impl Writable for $reg {}
+    };
+    ($reg:ty, $addr:ident, $access:ident) => {
+        const _: () = assert!(matches!(Registers::$addr.access(), Access::$access));
+
+        register!(@access $reg, $access);
+
+        impl Register for $reg {
+            const ADDR: Registers = Registers::$addr;
+            const ACCESS: Access = Access::$access;
+
+            fn from_u32(val: u32) -> Self {
+                Self::from_bytes(val.to_le_bytes())
+            }
+
+            fn to_u32(&self) -> u32 {
+                u32::from_le_bytes(self.into_bytes())
+            }
+        }
+    };
 }
 
 
@@ -189,6 +309,8 @@ pub struct DrvStatus {
     pub standstill: bool,
 }
 
+register!(DrvStatus, DRV_STATUS, R);
+
 /// GCONF Register
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -216,6 +338,48 @@ pub struct GConf {
     #[skip] _fill: B14,
 }
 
+register!(GConf, GCONF, RW);
+
+/// XDIRECT Register, replaces XTARGET while `GConf::direct_mode` is set
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+#[bitfield(bits = 32)]
+#[derive(ToValue)]
+pub struct XDirect {
+    /// signed coil A current
+    pub coil_a: B9,
+    #[skip] _a: B7,
+    /// signed coil B current
+    pub coil_b: B9,
+    #[skip] _b: B7,
+}
+
+register!(XDirect, XTARGET, RW);
+
+impl XDirect {
+    /// maximum coil current, the currents are scaled by IHOLD
+    pub const MAX_CURRENT: i16 = 255;
+
+    /// coil currents in -255..=255, `None` if a current is out of range
+    pub fn from_currents(coil_a: i16, coil_b: i16) -> Option<Self> {
+        let range = -Self::MAX_CURRENT..=Self::MAX_CURRENT;
+        if !range.contains(&coil_a) || !range.contains(&coil_b) {
+            return None;
+        }
+        Some(XDirect::new().with_coil_a(coil_a as u16 & 0x1FF).with_coil_b(coil_b as u16 & 0x1FF))
+    }
+
+    /// signed coil A current
+    pub fn current_a(&self) -> i16 {
+        ((self.coil_a() << 7) as i16) >> 7
+    }
+
+    /// signed coil B current
+    pub fn current_b(&self) -> i16 {
+        ((self.coil_b() << 7) as i16) >> 7
+    }
+}
+
 /// GSTAT Register
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -228,6 +392,8 @@ pub struct GStat {
     #[skip] _fill: B29,
 }
 
+register!(GStat, GSTAT, WC);
+
 // IFCNT Register is disabled in SPI mode
 
 /// NODECONF Register
@@ -241,6 +407,8 @@ pub struct NodeConf {
     #[skip] _fill: B20,
 }
 
+register!(NodeConf, SLAVECONF, W);
+
 
 /// IOIN Register
 #[derive(Clone, Copy)]
@@ -256,8 +424,15 @@ pub struct IoIn {
     pub enc_n_dco_cfg6: bool,
     pub sd_mode: bool,
     pub swcomp_in: bool,
-    pub version: u8,
     #[skip] _fill: B16,
+    pub version: u8,
+}
+
+register!(IoIn, IOIN, R);
+
+impl IoIn {
+    /// VERSION of the TMC5160
+    pub const VERSION: u8 = 0x30;
 }
 
 
@@ -268,25 +443,59 @@ pub struct IoIn {
 #[derive(ToValue)]
 pub struct OtpProg {
     pub otpbit: B3,
+    #[skip] _a: B1,
     pub otpbyte: B2,
-    #[skip] __: B3,
+    #[skip] _b: B2,
     pub otpmagic: u8,
     #[skip] _fill: B16,
 }
 
+register!(OtpProg, OTP_PROG, W);
+
+impl OtpProg {
+    /// magic value that has to be written together with the bit to be programmed
+    pub const MAGIC: u8 = 0xBD;
+}
+
 /// OTPREAD
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
 #[bitfield(bits = 32)]
 #[derive(ToValue)]
 pub struct OtpRead {
+    /// reset default of the clock trim FCLKTRIM (factory programmed)
     pub otp_fclktrim: B5,
+    /// reset default of the short detection levels S2G_LEVEL and S2VS_LEVEL
     pub otp_s2_level: bool,
+    /// reset default of the break before make time BBMCLKS
     pub otp_bbm: bool,
+    /// reset default of the blank time TBL in CHOPCONF
     pub otp_tbl: bool,
     #[skip] _fill: B24,
 }
 
+register!(OtpRead, OTP_READ, R);
+
+impl OtpRead {
+    /// bit of `otp_tbl` in OTP byte 0
+    pub const TBL_BIT: u8 = 7;
+
+    /// reset default of S2G_LEVEL and S2VS_LEVEL in SHORT_CONF
+    pub fn s2_level(&self) -> u8 {
+        if self.otp_s2_level() { 12 } else { 6 }
+    }
+
+    /// reset default of BBMCLKS in DRV_CONF
+    pub fn bbm_clks(&self) -> u8 {
+        if self.otp_bbm() { 2 } else { 4 }
+    }
+
+    /// reset default of TBL in CHOPCONF (0b01 = 24 clocks, 0b10 = 36 clocks)
+    pub fn tbl(&self) -> u8 {
+        if self.otp_tbl() { 0b01 } else { 0b10 }
+    }
+}
+
 /// SHORT_CONF
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -302,6 +511,8 @@ pub struct ShortConf {
     #[skip] _fill: B13,
 }
 
+register!(ShortConf, SHORT_CONF, W);
+
 /// DrvConfRegister
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -323,6 +534,8 @@ pub struct DrvConf {
     #[skip] _fill: B10,
 }
 
+register!(DrvConf, DRV_CONF, W);
+
 /// OFFSET_READ
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -332,6 +545,23 @@ pub struct OffsetRead {
     pub phase_b: u8,
 }
 
+impl Readable for OffsetRead {}
+
+impl Register for OffsetRead {
+    const ADDR: Registers = Registers::OFFSET_READ;
+    const ACCESS: Access = Access::R;
+
+    fn from_u32(val: u32) -> Self {
+        // phase A is in the upper byte
+        Self::from_bytes([(val >> 8) as u8, val as u8])
+    }
+
+    fn to_u32(&self) -> u32 {
+        let bytes = self.into_bytes();
+        (bytes[0] as u32) << 8 | bytes[1] as u32
+    }
+}
+
 
 /// IHOLD_IRUN Register
 #[derive(Clone, Copy)]
@@ -351,6 +581,8 @@ pub struct IHoldIRun {
     #[skip] _d: B8,
 }
 
+register!(IHoldIRun, IHOLD_IRUN, W);
+
 /// RAMPMODE Register
 #[allow(dead_code)]
 pub enum RampMode {
@@ -386,6 +618,8 @@ pub struct SwMode {
     #[skip] _fill: B20,
 }
 
+register!(SwMode, SW_MODE, RW);
+
 
 /// RAMOSTAT Register
 #[derive(Clone, Copy)]
@@ -410,6 +644,8 @@ pub struct RampStat {
     #[skip] _fill: B18,
 }
 
+register!(RampStat, RAMP_STAT, RC);
+
 
 /// ENCMODE Register
 #[derive(Clone, Copy)]
@@ -431,6 +667,8 @@ pub struct EncMode {
     #[skip] _fill: B21,
 }
 
+register!(EncMode, ENCMODE, RW);
+
 /// ENC_STATUS Register
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -442,6 +680,138 @@ pub struct EncStatus {
     #[skip] _fill: B30,
 }
 
+register!(EncStatus, ENC_STATUS, WC);
+
+/// X_ENC Register
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+#[bitfield(bits = 32)]
+#[derive(ToValue)]
+pub struct XEnc {
+    pub x_enc: u32,
+}
+
+register!(XEnc, X_ENC, RW);
+
+impl XEnc {
+    /// signed encoder position in microsteps
+    pub fn position(&self) -> i32 {
+        self.x_enc() as i32
+    }
+}
+
+/// ENC_CONST Register
+///
+/// Factor added to X_ENC for every encoder count, 16 bit signed integer part and 16 bit fractional part.
+/// The fractional part counts in 1/65536 (binary mode) or in 1/10000 (decimal mode, `EncMode::enc_sel_decimal`).
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+#[bitfield(bits = 32)]
+#[derive(ToValue)]
+pub struct EncConst {
+    pub fractional: u16,
+    pub integer: u16,
+}
+
+register!(EncConst, ENC_CONST, W);
+
+impl EncConst {
+    /// factor for `microsteps` per `counts` encoder counts, negative for an encoder counting in reverse
+    ///
+    /// Returns `None` if the factor is out of range or `counts` is 0.
+    pub fn from_ratio(microsteps: i32, counts: i32, decimal: bool) -> Option<Self> {
+        if counts == 0 {
+            return None;
+        }
+        let one = if decimal { 10_000 } else { 65_536 };
+        Self::from_scaled(div_round(microsteps as i64 * one, counts as i64), decimal)
+    }
+
+    /// the factor multiplied by `numerator / denominator`, e.g. for another microstep resolution
+    ///
+    /// Returns `None` if the factor is out of range or `denominator` is 0.
+    pub fn rescale(&self, numerator: i32, denominator: i32, decimal: bool) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        Self::from_scaled(div_round(self.scaled(decimal) * numerator as i64, denominator as i64), decimal)
+    }
+
+    /// factor in 1/10000 (decimal mode) or 1/65536
+    fn scaled(&self, decimal: bool) -> i64 {
+        if decimal {
+            self.integer() as i16 as i64 * 10_000 + self.fractional() as i64
+        } else {
+            self.to_u32() as i32 as i64
+        }
+    }
+
+    fn from_scaled(scaled: i64, decimal: bool) -> Option<Self> {
+        let raw = if decimal {
+            let integer = i16::try_from(scaled.div_euclid(10_000)).ok()?;
+            (integer as u16 as u32) << 16 | scaled.rem_euclid(10_000) as u32
+        } else {
+            i32::try_from(scaled).ok()? as u32
+        };
+        Some(Self::from_u32(raw))
+    }
+
+    /// factor for an encoder with `encoder_lines` lines (4 counts per line) on a motor with `microsteps_per_rev`
+    ///
+    /// Returns `None` if the factor is out of range or `encoder_lines` is 0.
+    pub fn from_resolution(encoder_lines: u32, microsteps_per_rev: i32, decimal: bool) -> Option<Self> {
+        let counts = i32::try_from(encoder_lines.checked_mul(4)?).ok()?;
+        Self::from_ratio(microsteps_per_rev, counts, decimal)
+    }
+
+    /// microsteps per encoder count
+    pub fn factor(&self, decimal: bool) -> f32 {
+        let integer = self.integer() as i16 as f32;
+        if decimal {
+            integer + self.fractional() as f32 / 10_000.0
+        } else {
+            integer + self.fractional() as f32 / 65_536.0
+        }
+    }
+}
+
+/// integer division rounding to the nearest value
+fn div_round(numerator: i64, denominator: i64) -> i64 {
+    let (numerator, denominator) = if denominator < 0 { (-numerator, -denominator) } else { (numerator, denominator) };
+    (2 * numerator + denominator).div_euclid(2 * denominator)
+}
+
+/// ENC_LATCH Register
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+#[bitfield(bits = 32)]
+#[derive(ToValue)]
+pub struct EncLatch {
+    pub enc_latch: u32,
+}
+
+register!(EncLatch, ENC_LATCH, R);
+
+impl EncLatch {
+    /// signed latched encoder position in microsteps
+    pub fn position(&self) -> i32 {
+        self.enc_latch() as i32
+    }
+}
+
+/// ENC_DEVIATION Register
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+#[bitfield(bits = 32)]
+#[derive(ToValue)]
+pub struct EncDeviation {
+    /// maximum deviation between XACTUAL and X_ENC in microsteps before `deviation_warn` is set, 0 disables it
+    pub enc_deviation: B20,
+    #[skip] _fill: B12,
+}
+
+register!(EncDeviation, ENC_DEVIATION, W);
+
 
 /// MSLUTSEL Register
 #[derive(Clone, Copy)]
@@ -458,6 +828,24 @@ pub struct MsLutSel {
     pub x3: u8,
 }
 
+register!(MsLutSel, MSLUTSEL, W);
+
+/// MSLUTSTART Register
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+#[bitfield(bits = 32)]
+#[derive(ToValue)]
+pub struct MsLutStart {
+    /// absolute current at microstep table entry 0
+    pub start_sin: u8,
+    #[skip] _a: u8,
+    /// absolute current for the cosine wave at microstep table entry 0
+    pub start_sin90: u8,
+    #[skip] _b: u8,
+}
+
+register!(MsLutStart, MSLUTSTART, W);
+
 /// CHOPFCONF Register
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -483,12 +871,77 @@ pub struct ChopConf {
     pub diss2vs: bool,
 }
 
+register!(ChopConf, CHOPCONF, RW);
+
 impl Default for ChopConf {
     fn default() -> Self {
         Self::from_bytes(0x10410150_u32.to_le_bytes())
     }
 }
 
+impl ChopConf {
+    /// microstep resolution selected by MRES, `None` for the reserved values
+    pub fn resolution(&self) -> Option<MicrostepResolution> {
+        MicrostepResolution::from_mres(self.mres())
+    }
+
+    /// set MRES to a microstep resolution
+    pub fn set_resolution(&mut self, resolution: MicrostepResolution) {
+        self.set_mres(resolution as u8);
+    }
+
+    /// builder for MRES from a microstep resolution
+    pub fn with_resolution(mut self, resolution: MicrostepResolution) -> Self {
+        self.set_resolution(resolution);
+        self
+    }
+}
+
+/// microstep resolution, value of MRES in CHOPCONF
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MicrostepResolution {
+    /// 256 microsteps per full step (Default)
+    M256 = 0,
+    /// 128 microsteps per full step
+    M128 = 1,
+    /// 64 microsteps per full step
+    M64 = 2,
+    /// 32 microsteps per full step
+    M32 = 3,
+    /// 16 microsteps per full step
+    M16 = 4,
+    /// 8 microsteps per full step
+    M8 = 5,
+    /// 4 microsteps per full step
+    M4 = 6,
+    /// 2 microsteps per full step
+    M2 = 7,
+    /// full steps
+    FullStep = 8,
+}
+
+impl MicrostepResolution {
+    /// resolution for a MRES value, `None` for the reserved values
+    pub fn from_mres(mres: u8) -> Option<Self> {
+        use MicrostepResolution::*;
+        [M256, M128, M64, M32, M16, M8, M4, M2, FullStep].get(mres as usize).copied()
+    }
+
+    /// resolution with `microsteps` per full step, `None` if it is not a power of two up to 256
+    pub fn from_microsteps(microsteps: u32) -> Option<Self> {
+        if microsteps.is_power_of_two() && microsteps <= 256 {
+            Self::from_mres(8 - microsteps.trailing_zeros() as u8)
+        } else {
+            None
+        }
+    }
+
+    /// microsteps per full step
+    pub fn microsteps(self) -> u32 {
+        256 >> self as u32
+    }
+}
+
 
 /// COOLCONF Register
 #[derive(Clone, Copy)]
@@ -510,6 +963,35 @@ pub struct CoolConf {
     #[skip] _e: B8,
 }
 
+register!(CoolConf, COOLCONF, W);
+
+/// DCCTRL Register
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+#[bitfield(bits = 32)]
+#[derive(ToValue)]
+pub struct DcCtrl {
+    /// upper PWM on time limit for commutation in clock cycles, set slightly above the blank time
+    pub dc_time: B10,
+    #[skip] _a: B6,
+    /// max. PWM on time for step loss detection in units of 16 clock cycles, 0 disables it
+    pub dc_sg: u8,
+    #[skip] _b: B8,
+}
+
+register!(DcCtrl, DCCTRL, W);
+
+impl DcCtrl {
+    /// timing recommended by the datasheet for the blank time `tbl` of CHOPCONF
+    ///
+    /// DC_TIME is set slightly above the blank time and DC_SG slightly above DC_TIME / 16.
+    pub fn from_blank_time(tbl: u8) -> Self {
+        let blank_clocks = [16, 24, 36, 54][tbl as usize & 0b11];
+        let dc_time = blank_clocks + 1;
+        DcCtrl::new().with_dc_time(dc_time).with_dc_sg((dc_time / 16 + 1) as u8)
+    }
+}
+
 /// PWMCONF Register
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -527,8 +1009,92 @@ pub struct PwmConf {
     pub pwm_lim: B4,
 }
 
+register!(PwmConf, PWMCONF, W);
+
+/// LOST_STEPS Register
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+#[bitfield(bits = 32)]
+#[derive(ToValue)]
+pub struct LostSteps {
+    /// steps skipped in dcStep operation, counts up or down depending on the direction and wraps at 2^20
+    pub lost_steps: B20,
+    #[skip] _fill: B12,
+}
+
+register!(LostSteps, LOST_STEPS, R);
+
+impl LostSteps {
+    /// signed number of skipped steps
+    pub fn steps(&self) -> i32 {
+        ((self.lost_steps() << 12) as i32) >> 12
+    }
+}
+
 impl Default for PwmConf {
     fn default() -> Self {
         Self::from_bytes(0xC40C001E_u32.to_le_bytes())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn x_direct_sign_extension() {
+        for current in [-255, -128, -1, 0, 1, 128, 255] {
+            let x_direct = XDirect::from_currents(current, -current).unwrap();
+            assert_eq!((x_direct.current_a(), x_direct.current_b()), (current, -current));
+        }
+        let x_direct = XDirect::from_currents(-1, 255).unwrap();
+        assert_eq!(x_direct.to_u32(), 0x00FF_01FF);
+    }
+
+    #[test]
+    fn x_direct_refused_currents() {
+        assert!(XDirect::from_currents(256, 0).is_none());
+        assert!(XDirect::from_currents(0, -256).is_none());
+    }
+
+    #[test]
+    fn enc_const_binary() {
+        let enc_const = EncConst::from_ratio(3, 2, false).unwrap();
+        assert_eq!((enc_const.integer(), enc_const.fractional()), (1, 0x8000));
+        let enc_const = EncConst::from_ratio(-3, 2, false).unwrap();
+        assert_eq!(enc_const.to_u32(), 0xFFFE_8000);
+        assert_eq!(enc_const.factor(false), -1.5);
+    }
+
+    #[test]
+    fn enc_const_decimal() {
+        // 200 fullsteps with 256 microsteps on an encoder with 4000 lines
+        let enc_const = EncConst::from_resolution(4000, 51_200, true).unwrap();
+        assert_eq!((enc_const.integer(), enc_const.fractional()), (3, 2000));
+        // -1.5 is stored as -2 + 0.5
+        let enc_const = EncConst::from_ratio(-3, 2, true).unwrap();
+        assert_eq!((enc_const.integer() as i16, enc_const.fractional()), (-2, 5000));
+        assert_eq!(enc_const.factor(true), -1.5);
+        let enc_const = EncConst::from_ratio(1, -3, true).unwrap();
+        assert_eq!((enc_const.integer() as i16, enc_const.fractional()), (-1, 6667));
+    }
+
+    #[test]
+    fn enc_const_refused_ratios() {
+        assert!(EncConst::from_ratio(1, 0, false).is_none());
+        assert!(EncConst::from_resolution(0, 51_200, true).is_none());
+        assert!(EncConst::from_ratio(1 << 15, 1, false).is_none());
+        assert!(EncConst::from_ratio(-(1 << 15), 1, false).is_some());
+        assert!(EncConst::from_ratio(40_000, 1, true).is_none());
+    }
+
+    #[test]
+    fn enc_const_rescale() {
+        let enc_const = EncConst::from_ratio(-3, 2, true).unwrap();
+        let rescaled = enc_const.rescale(1, 16, true).unwrap();
+        assert_eq!((rescaled.integer() as i16, rescaled.fractional()), (-1, 9063));
+        let enc_const = EncConst::from_ratio(256, 1, false).unwrap();
+        assert_eq!(enc_const.rescale(16, 256, false).unwrap().to_u32(), 16 << 16);
+        assert!(EncConst::from_ratio(1, 1, false).unwrap().rescale(1 << 15, 1, false).is_none());
+        assert!(enc_const.rescale(1, 0, false).is_none());
+    }
+}
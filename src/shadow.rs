@@ -0,0 +1,157 @@
+//! Shadow copy of the writable configuration registers
+//!
+//! The shadow records the last value of every configuration register that has been written to the chip, plus
+//! values that have been staged with [`crate::Tmc5160::stage`] but not written yet (dirty). Registers that trigger an action when written
+//! (`GSTAT`, `ENC_STATUS`, `OTP_PROG`), hold positions (`XACTUAL`, `XTARGET`, `X_ENC`), the factory clock trim
+//! (`FACTORY_CONF`) and the UART node address (`SLAVECONF`) are not part of the shadow.
+
+use crate::registers::{Address, Registers, Writable};
+
+/// MSLUT0 to MSLUT7 are at consecutive addresses
+const MSLUT: u8 = Registers::MSLUT_0_7 as u8;
+
+/// shadowed registers in the order they are written by [`crate::Tmc5160::commit`] and
+/// [`crate::Tmc5160::resync`], the chopper is enabled and the ramp generator started last
+const ADDRS: [u8; 39] = [
+    Registers::GCONF as u8,
+    Registers::SHORT_CONF as u8,
+    Registers::DRV_CONF as u8,
+    Registers::GLOBALSCALER as u8,
+    Registers::IHOLD_IRUN as u8,
+    Registers::TPOWERDOWN as u8,
+    Registers::TPWMTHRS as u8,
+    Registers::TCOOLTHRS as u8,
+    Registers::THIGH as u8,
+    Registers::X_COMPARE as u8,
+    Registers::VDCMIN as u8,
+    Registers::SW_MODE as u8,
+    Registers::ENCMODE as u8,
+    Registers::ENC_CONST as u8,
+    Registers::ENC_DEVIATION as u8,
+    MSLUT,
+    MSLUT + 1,
+    MSLUT + 2,
+    MSLUT + 3,
+    MSLUT + 4,
+    MSLUT + 5,
+    MSLUT + 6,
+    MSLUT + 7,
+    Registers::MSLUTSEL as u8,
+    Registers::MSLUTSTART as u8,
+    Registers::COOLCONF as u8,
+    Registers::DCCTRL as u8,
+    Registers::PWMCONF as u8,
+    Registers::CHOPCONF as u8,
+    Registers::VSTART as u8,
+    Registers::A1 as u8,
+    Registers::V1 as u8,
+    Registers::AMAX as u8,
+    Registers::VMAX as u8,
+    Registers::DMAX as u8,
+    Registers::D1 as u8,
+    Registers::VSTOP as u8,
+    Registers::TZEROWAIT as u8,
+    Registers::RAMPMODE as u8,
+];
+
+/// Shadow copy of the writable configuration registers with dirty tracking
+pub struct Shadow {
+    values: [u32; ADDRS.len()],
+    /// a value has been set or written
+    known: u64,
+    /// the value has not been written to the chip yet
+    dirty: u64,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shadow {
+    /// Create an empty shadow, no register value is known
+    pub const fn new() -> Self {
+        Shadow { values: [0; ADDRS.len()], known: 0, dirty: 0 }
+    }
+
+    fn index(addr: u8) -> Option<usize> {
+        ADDRS.iter().position(|&shadowed| shadowed == addr & 0x7F)
+    }
+
+    /// the register is part of the shadow
+    pub fn contains<T: Address>(reg: T) -> bool {
+        Self::index(reg.addr()).is_some()
+    }
+
+    /// value of a typed register, `None` if it has never been set or written
+    pub fn get<R: Writable>(&self) -> Option<R> {
+        self.get_raw(R::ADDR).map(R::from_u32)
+    }
+
+    /// value of a register, `None` if it has never been set or written
+    pub fn get_raw<T: Address>(&self, reg: T) -> Option<u32> {
+        Self::index(reg.addr())
+            .filter(|&i| self.known & 1 << i != 0)
+            .map(|i| self.values[i])
+    }
+
+    /// set a register, it is written by the next commit if the value changed
+    ///
+    /// Returns false if the register is not part of the shadow.
+    pub(crate) fn set(&mut self, addr: u8, val: u32) -> bool {
+        match Self::index(addr) {
+            Some(i) => {
+                if self.known & 1 << i == 0 || self.values[i] != val {
+                    self.values[i] = val;
+                    self.known |= 1 << i;
+                    self.dirty |= 1 << i;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// the register has been set but not written yet
+    pub fn is_dirty<T: Address>(&self, reg: T) -> bool {
+        Self::index(reg.addr()).is_some_and(|i| self.dirty & 1 << i != 0)
+    }
+
+    /// any register has been set but not written yet
+    pub fn has_dirty(&self) -> bool {
+        self.dirty != 0
+    }
+
+    /// mark all known registers dirty, e.g. after the chip lost its configuration
+    pub(crate) fn invalidate(&mut self) {
+        self.dirty = self.known;
+    }
+
+    /// record a value written to the chip
+    pub(crate) fn written(&mut self, addr: u8, val: u32) {
+        if let Some(i) = Self::index(addr) {
+            self.values[i] = val;
+            self.known |= 1 << i;
+            self.dirty &= !(1 << i);
+        }
+    }
+
+    /// set a register only if the value differs from the shadow, unknown registers are compared against 0
+    pub(crate) fn stage(&mut self, addr: u8, val: u32) {
+        if let Some(i) = Self::index(addr) {
+            if self.values[i] != val {
+                self.values[i] = val;
+                self.known |= 1 << i;
+                self.dirty |= 1 << i;
+            }
+        }
+    }
+
+    /// next dirty register in write order
+    pub(crate) fn next_dirty(&self) -> Option<(u8, u32)> {
+        (0..ADDRS.len())
+            .find(|&i| self.dirty & 1 << i != 0)
+            .map(|i| (ADDRS[i], self.values[i]))
+    }
+}
@@ -0,0 +1,447 @@
+//! Step/Dir operation (SD_MODE high)
+//!
+//! With SD_MODE strapped high the internal ramp generator is off, the motor is moved by pulses on STEP and DIR and
+//! SPI or UART is only used for configuration. [`StepDirDriver`] exposes the chopper, current and diagnostic features
+//! of the TMC5160 without the ramp generator. [`StepGenerator`] creates the pulses with two embedded-hal pins and a
+//! delay as timer. The [`Motion`] trait is implemented by the generator and by [`crate::Tmc5160`], so motion code
+//! works in both modes:
+//!
+//! ```ignore
+//! fn home<M: Motion>(motion: &mut M) -> Result<(), M::Error> {
+//!     motion.set_velocity(50.0)?;
+//!     motion.move_to(0.0)
+//! }
+//! ```
+
+use core::convert::Infallible;
+use core::result::Result;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::SpiDevice;
+
+use crate::registers::*;
+use crate::shadow::Shadow;
+use crate::transport::{SpiTransport, Transport};
+use crate::units::Microsteps;
+use crate::{conversion, ChipInfo, DataPacket, Error, ResetPolicy, Tmc5160};
+
+/// Motion commands shared by the ramp generator of the TMC5160 and an external step generator
+pub trait Motion {
+    /// Error of the motion backend
+    type Error;
+
+    /// set the velocity in steps per second
+    fn set_velocity(&mut self, velocity: f32) -> Result<(), Self::Error>;
+
+    /// move to the target position in steps
+    fn move_to(&mut self, target: f32) -> Result<(), Self::Error>;
+
+    /// current position in steps
+    fn get_position(&mut self) -> Result<f32, Self::Error>;
+}
+
+impl<BUS, EN, E> Motion for Tmc5160<BUS, EN>
+    where
+        BUS: Transport<Error=E>,
+        EN: OutputPin,
+{
+    type Error = Error<E>;
+
+    fn set_velocity(&mut self, velocity: f32) -> Result<(), Error<E>> {
+        Tmc5160::set_velocity(self, velocity).map(|_| ())
+    }
+
+    /// starts the move, the ramp generator runs in the background
+    fn move_to(&mut self, target: f32) -> Result<(), Error<E>> {
+        Tmc5160::move_to(self, target).map(|_| ())
+    }
+
+    fn get_position(&mut self) -> Result<f32, Error<E>> {
+        Tmc5160::get_position(self)
+    }
+}
+
+/// Step pulse generator on a STEP and a DIR pin, the delay is used as timer
+///
+/// Moves run at constant velocity and block until the target is reached, the position is counted in software.
+pub struct StepGenerator<STEP, DIR, D> {
+    step: STEP,
+    dir: DIR,
+    delay: D,
+    /// position in pulses
+    position: i32,
+    /// velocity in steps per second
+    velocity: f32,
+    _microsteps: u32,
+    _dir_inverted: bool,
+}
+
+impl<STEP, DIR, D> StepGenerator<STEP, DIR, D>
+    where
+        STEP: OutputPin,
+        DIR: OutputPin,
+        D: DelayNs,
+{
+    /// Create a new step generator
+    pub fn new(step: STEP, dir: DIR, delay: D) -> Self {
+        StepGenerator {
+            step,
+            dir,
+            delay,
+            position: 0,
+            velocity: 0.0,
+            _microsteps: 256,
+            _dir_inverted: false,
+        }
+    }
+
+    /// specify the pulses per step with MRES of `chop_conf` (Default is 256)
+    pub fn resolution(mut self, chop_conf: &ChopConf) -> Self {
+        self.set_resolution(chop_conf);
+        self
+    }
+
+    /// follow MRES of `chop_conf` after CHOPCONF has been written, the position is rescaled to the new resolution
+    ///
+    /// A reserved MRES is ignored.
+    pub fn set_resolution(&mut self, chop_conf: &ChopConf) {
+        if let Some(microsteps) = chop_conf.resolution().map(MicrostepResolution::microsteps) {
+            let position = conversion::rescale(Microsteps(self.position), self._microsteps, microsteps);
+            self.position = position.map_or(self.position, |position| position.0);
+            self._microsteps = microsteps;
+        }
+    }
+
+    /// invert the DIR pin
+    pub fn dir_inverted(mut self, inv: bool) -> Self {
+        self._dir_inverted = inv;
+        self
+    }
+
+    /// release the pins and the delay
+    pub fn release(self) -> (STEP, DIR, D) {
+        (self.step, self.dir, self.delay)
+    }
+
+    /// set the current position in steps without moving
+    pub fn set_position(&mut self, position: f32) {
+        self.position = (position * self._microsteps as f32) as i32;
+    }
+
+    /// send `pulses` step pulses, negative to move backwards
+    pub fn step(&mut self, pulses: i32) -> Result<(), Error<Infallible>> {
+        if pulses == 0 {
+            return Ok(());
+        }
+        if self.velocity <= 0.0 {
+            return Err(Error::OutOfRange);
+        }
+        let forward = (pulses > 0) != self._dir_inverted;
+        if forward {
+            self.dir.set_high().map_err(|_| Error::PinError)?;
+        } else {
+            self.dir.set_low().map_err(|_| Error::PinError)?;
+        }
+        let half_period_ns = (500_000_000.0 / (self.velocity * self._microsteps as f32)) as u32;
+        for _ in 0..pulses.unsigned_abs() {
+            self.step.set_high().map_err(|_| Error::PinError)?;
+            self.delay.delay_ns(half_period_ns);
+            self.step.set_low().map_err(|_| Error::PinError)?;
+            self.delay.delay_ns(half_period_ns);
+            self.position += pulses.signum();
+        }
+        Ok(())
+    }
+}
+
+impl<STEP, DIR, D> Motion for StepGenerator<STEP, DIR, D>
+    where
+        STEP: OutputPin,
+        DIR: OutputPin,
+        D: DelayNs,
+{
+    type Error = Error<Infallible>;
+
+    fn set_velocity(&mut self, velocity: f32) -> Result<(), Error<Infallible>> {
+        self.velocity = velocity;
+        Ok(())
+    }
+
+    /// blocks until the target is reached, fails with [`Error::OutOfRange`] if no velocity has been set
+    fn move_to(&mut self, target: f32) -> Result<(), Error<Infallible>> {
+        let target = (target * self._microsteps as f32) as i32;
+        self.step(target.wrapping_sub(self.position))
+    }
+
+    fn get_position(&mut self) -> Result<f32, Error<Infallible>> {
+        Ok(self.position as f32 / self._microsteps as f32)
+    }
+}
+
+/// TMC5160 with SD_MODE high, only the chopper, current and diagnostic features are available
+pub struct StepDirDriver<BUS, EN> {
+    driver: Tmc5160<BUS, EN>,
+}
+
+impl<SPI, EN, E> StepDirDriver<SpiTransport<SPI>, EN>
+    where
+        SPI: SpiDevice<u8, Error=E>,
+        EN: OutputPin,
+{
+    /// Create a new driver from a SPI device, chip select is handled by the device
+    pub fn new(spi: SPI) -> Self {
+        Self::with_transport(SpiTransport::new(spi))
+    }
+}
+
+impl<BUS, EN, E> StepDirDriver<BUS, EN>
+    where
+        BUS: Transport<Error=E>,
+        EN: OutputPin,
+{
+    /// Create a new driver on top of a register transport
+    pub fn with_transport(bus: BUS) -> Self {
+        StepDirDriver { driver: Tmc5160::with_transport(bus).sd_mode() }
+    }
+
+    /// attach an enable pin to the driver
+    pub fn attach_en(self, en: EN) -> Self {
+        StepDirDriver { driver: self.driver.attach_en(en) }
+    }
+
+    /// invert the enable pin
+    pub fn en_inverted(self, inv: bool) -> Self {
+        StepDirDriver { driver: self.driver.en_inverted(inv) }
+    }
+
+    /// specify the reaction on a reset of the chip (Default is [`ResetPolicy::Ignore`])
+    pub fn reset_policy(self, policy: ResetPolicy) -> Self {
+        StepDirDriver { driver: self.driver.reset_policy(policy) }
+    }
+
+    /// on UART read GSTAT every `accesses` register accesses to detect a reset (Default is 0, no polling)
+    pub fn reset_poll(self, accesses: u32) -> Self {
+        StepDirDriver { driver: self.driver.reset_poll(accesses) }
+    }
+
+    /// release the transport
+    pub fn release(self) -> BUS {
+        self.driver.release()
+    }
+
+    /// status of the last transmission
+    pub fn status(&self) -> SpiStatus {
+        self.driver.status
+    }
+
+    /// value of the GCONF register
+    pub fn g_conf(&mut self) -> &mut GConf {
+        &mut self.driver.g_conf
+    }
+
+    /// value of the CHOPCONF register, pass it to [`StepGenerator::set_resolution`] after it has been written
+    pub fn chop_conf(&mut self) -> &mut ChopConf {
+        &mut self.driver.chop_conf
+    }
+
+    /// value of the IHOLD_IRUN register
+    pub fn ihold_irun(&mut self) -> &mut IHoldIRun {
+        &mut self.driver.ihold_irun
+    }
+
+    /// value of the COOLCONF register
+    pub fn cool_conf(&mut self) -> &mut CoolConf {
+        &mut self.driver.cool_conf
+    }
+
+    /// value of the PWMCONF register
+    pub fn pwm_conf(&mut self) -> &mut PwmConf {
+        &mut self.driver.pwm_conf
+    }
+
+    /// read a typed register
+    pub fn read<R>(&mut self) -> Result<R, Error<E>>
+        where
+            R: Readable,
+    {
+        self.driver.read::<R>()
+    }
+
+    /// write a typed register
+    pub fn write<R>(&mut self, reg: &R) -> Result<DataPacket, Error<E>>
+        where
+            R: Writable,
+    {
+        self.driver.write(reg)
+    }
+
+    /// shadow copy of the configuration registers
+    pub fn shadow(&self) -> &Shadow {
+        self.driver.shadow()
+    }
+
+    /// write all staged registers and changed register values to the chip
+    pub fn commit(&mut self) -> Result<usize, Error<E>> {
+        self.driver.commit()
+    }
+
+    /// restore the chip after a reset: clear GSTAT and write all known configuration registers again
+    pub fn recover(&mut self) -> Result<usize, Error<E>> {
+        self.driver.recover()
+    }
+
+    /// enable the motor if the EN pin was specified
+    pub fn enable(&mut self) -> Result<(), Error<E>> {
+        self.driver.enable()
+    }
+
+    /// disable the motor if the EN pin was specified
+    pub fn disable(&mut self) -> Result<(), Error<E>> {
+        self.driver.disable()
+    }
+
+    /// write value to G_CONF register
+    pub fn update_g_conf(&mut self) -> Result<DataPacket, Error<E>> {
+        self.driver.update_g_conf()
+    }
+
+    /// write value to CHOP_CONF register
+    pub fn update_chop_conf(&mut self) -> Result<DataPacket, Error<E>> {
+        self.driver.update_chop_conf()
+    }
+
+    /// write value to IHOLD_IRUN register
+    pub fn update_ihold_irun(&mut self) -> Result<DataPacket, Error<E>> {
+        self.driver.update_ihold_irun()
+    }
+
+    /// write value to COOL_CONF register
+    pub fn update_cool_conf(&mut self) -> Result<DataPacket, Error<E>> {
+        self.driver.update_cool_conf()
+    }
+
+    /// write value to PWM_CONF register
+    pub fn update_pwm_conf(&mut self) -> Result<DataPacket, Error<E>> {
+        self.driver.update_pwm_conf()
+    }
+
+    /// write value to GLOBALSCALER register
+    pub fn set_global_scaler(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+        self.driver.set_global_scaler(val)
+    }
+
+    /// write value to TPOWERDOWN register
+    pub fn set_tpowerdown(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+        self.driver.set_tpowerdown(val)
+    }
+
+    /// write value to TPWMTHRS register
+    pub fn set_tpwmthrs(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+        self.driver.set_tpwmthrs(val)
+    }
+
+    /// write value to TCOOLTHRS register
+    pub fn set_tcoolthrs(&mut self, val: u32) -> Result<DataPacket, Error<E>> {
+        self.driver.set_tcoolthrs(val)
+    }
+
+    /// set the dcStep timing (DCCTRL) in seconds, dcStep is enabled by the DCEN pin in Step/Dir mode
+    pub fn set_dc_step_timing(&mut self, on_time: f32, stall_time: f32) -> Result<DataPacket, Error<E>> {
+        self.driver.set_dc_step_timing(on_time, stall_time)
+    }
+
+    /// clear G_STAT register
+    pub fn clear_g_stat(&mut self) -> Result<DataPacket, Error<E>> {
+        self.driver.clear_g_stat()
+    }
+
+    /// read GSTAT register
+    pub fn read_gstat(&mut self) -> Result<GStat, Error<E>> {
+        self.driver.read_gstat()
+    }
+
+    /// read DRV_STATUS register
+    pub fn read_drv_status(&mut self) -> Result<DrvStatus, Error<E>> {
+        self.driver.read_drv_status()
+    }
+
+    /// read IOIN register
+    pub fn read_ioin(&mut self) -> Result<IoIn, Error<E>> {
+        self.driver.read_ioin()
+    }
+
+    /// check that a TMC5160 answers and read the state of its pins
+    pub fn identify(&mut self) -> Result<ChipInfo, Error<E>> {
+        self.driver.identify()
+    }
+
+    /// read OFFSET_READ register
+    pub fn read_offset(&mut self) -> Result<u32, Error<E>> {
+        self.driver.read_offset()
+    }
+
+    /// read TSTEP register, the time between two step pulses
+    pub fn read_tstep(&mut self) -> Result<u32, Error<E>> {
+        self.driver.read_tstep()
+    }
+
+    /// read PWM_AUTO register
+    pub fn read_pwm_auto(&mut self) -> Result<u32, Error<E>> {
+        self.driver.read_pwm_auto()
+    }
+
+    /// read LOST_STEPS register, the steps skipped in dcStep operation
+    pub fn read_lost_steps(&mut self) -> Result<i32, Error<E>> {
+        self.driver.read_lost_steps()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Access, Delay, Pin, Recorder};
+
+    fn generator(resolution: MicrostepResolution) -> StepGenerator<Pin, Pin, Delay> {
+        let chop_conf = ChopConf::new().with_resolution(resolution);
+        StepGenerator::new(Pin::default(), Pin::default(), Delay::default()).resolution(&chop_conf)
+    }
+
+    #[test]
+    fn step_pulses() {
+        let mut generator = generator(MicrostepResolution::M16);
+        assert!(matches!(generator.step(1), Err(Error::OutOfRange)));
+        generator.set_velocity(100.0).unwrap();
+        generator.step(-3).unwrap();
+        assert_eq!(generator.get_position().unwrap(), -3.0 / 16.0);
+        let (step, dir, delay) = generator.release();
+        assert_eq!(dir.levels, [false]);
+        assert_eq!(step.levels, [true, false, true, false, true, false]);
+        // 1600 pulses per second
+        assert_eq!(delay.ns, 3 * 625_000);
+    }
+
+    #[test]
+    fn move_follows_the_resolution() {
+        let mut generator = generator(MicrostepResolution::M16).dir_inverted(true);
+        generator.set_velocity(1000.0).unwrap();
+        generator.move_to(1.0).unwrap();
+        assert_eq!(generator.position, 16);
+        generator.set_resolution(&ChopConf::new().with_resolution(MicrostepResolution::M256));
+        assert_eq!(generator.position, 256);
+        generator.move_to(1.5).unwrap();
+        assert_eq!(generator.get_position().unwrap(), 1.5);
+        let (step, dir, _) = generator.release();
+        assert_eq!(dir.levels, [false, false]);
+        assert_eq!(step.levels.len(), 2 * (16 + 128));
+    }
+
+    #[test]
+    fn resolution_change_does_not_touch_the_positions() {
+        let mut driver: StepDirDriver<Recorder, Pin> = StepDirDriver::with_transport(Recorder::new());
+        driver.chop_conf().set_resolution(MicrostepResolution::M16);
+        driver.update_chop_conf().unwrap();
+        let chop_conf = driver.chop_conf().to_u32();
+        assert_eq!(driver.release().log, [Access::Write(Registers::CHOPCONF as u8, chop_conf)]);
+    }
+}
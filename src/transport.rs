@@ -0,0 +1,191 @@
+//! Register transports of the TMC5160
+//!
+//! The high-level [`crate::Tmc5160`] API only needs to read and write 32 bit registers, the [`Transport`] trait
+//! abstracts over the physical interface that is used for this. The crate ships transports for a [`SpiDevice`]
+//! ([`SpiTransport`]), UART ([`crate::uart::UartTransport`]), a device in a daisy chain
+//! ([`crate::chain::ChainDevice`]) and embedded-hal 0.2 buses (`eh0::SpiTransport`).
+//!
+//! Any other transport, e.g. a mock recording all accesses, can be plugged in by implementing the trait:
+//!
+//! ```ignore
+//! struct Recorder {
+//!     writes: heapless::Vec<(u8, u32), 64>,
+//! }
+//!
+//! impl Transport for Recorder {
+//!     type Error = ();
+//!
+//!     fn read_register(&mut self, _addr: u8) -> Result<DataPacket, Error<()>> {
+//!         Ok(DataPacket { status: SpiStatus::new(), data: 0, debug: [0; 5] })
+//!     }
+//!
+//!     fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<()>> {
+//!         self.writes.push((addr, u32::from_be_bytes(val))).ok();
+//!         Ok(DataPacket { status: SpiStatus::new(), data: 0, debug: [0; 5] })
+//!     }
+//! }
+//!
+//! let mut stepper_driver = Tmc5160::with_transport(Recorder { writes: heapless::Vec::new() });
+//! ```
+
+use core::result::Result;
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::registers::SpiStatus;
+use crate::{DataPacket, Error};
+
+/// Interface of the TMC5160
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interface {
+    /// SPI_MODE high
+    Spi,
+    /// SPI_MODE low, single wire UART
+    Uart,
+}
+
+/// Register access to a TMC5160 over an arbitrary interface
+pub trait Transport {
+    /// Error of the underlying bus
+    type Error;
+
+    /// read the register at the given address
+    fn read_register(&mut self, addr: u8) -> Result<DataPacket, Error<Self::Error>>;
+
+    /// write `val` (MSB first) to the register at the given address
+    fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<Self::Error>>;
+
+    /// interface the chip is accessed by, the chip answers only on the interface selected by its SPI_MODE pin
+    fn interface(&self) -> Interface {
+        Interface::Spi
+    }
+
+    /// read the registers at the given addresses, transports that can pipeline requests override this
+    fn read_registers<const N: usize>(&mut self, addrs: [u8; N]) -> Result<[DataPacket; N], Error<Self::Error>> {
+        let mut packets = [DataPacket::EMPTY; N];
+        for (packet, addr) in packets.iter_mut().zip(addrs) {
+            *packet = self.read_register(addr)?;
+        }
+        Ok(packets)
+    }
+}
+
+/// frames of a pipelined SPI read of `addrs`, every frame returns the data requested by the previous one
+///
+/// Yields N + 1 addresses to send together with the index in `addrs` the reply belongs to, the reply to the first
+/// frame is a dummy (`None`) and the last address is sent twice to fetch its data.
+pub(crate) fn pipeline<const N: usize>(addrs: [u8; N]) -> impl Iterator<Item = (u8, Option<usize>)> {
+    let last = addrs.last().map(|&addr| (addr, Some(N - 1)));
+    addrs.into_iter().enumerate().map(|(index, addr)| (addr, index.checked_sub(1))).chain(last)
+}
+
+/// pipelined SPI read of `addrs` taking N + 1 frames, see [`pipeline`]
+pub(crate) fn read_pipelined<E, F, const N: usize>(addrs: [u8; N], mut read_io: F) -> Result<[DataPacket; N], Error<E>>
+    where
+        F: FnMut(u8) -> Result<DataPacket, Error<E>>,
+{
+    let mut packets = [DataPacket::EMPTY; N];
+    for (addr, index) in pipeline(addrs) {
+        let packet = read_io(addr)?;
+        if let Some(index) = index {
+            packets[index] = packet;
+        }
+    }
+    Ok(packets)
+}
+
+/// SPI transport, chip select is handled by the [`SpiDevice`]
+pub struct SpiTransport<SPI> {
+    spi: SPI,
+}
+
+impl<SPI, E> SpiTransport<SPI>
+    where
+        SPI: SpiDevice<u8, Error=E>,
+{
+    /// Create a new transport from a SPI device
+    pub fn new(spi: SPI) -> Self {
+        SpiTransport { spi }
+    }
+
+    /// release the SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    fn read_io(&mut self, addr: u8) -> Result<DataPacket, Error<E>> {
+        let mut buffer = [addr, 0, 0, 0, 0];
+
+        self.spi.transfer_in_place(&mut buffer).map_err(Error::Spi)?;
+
+        let response = buffer;
+
+        Ok(DataPacket {
+            status: SpiStatus::from_bytes([response[0]]),
+            data: u32::from_be_bytes([response[1], response[2], response[3], response[4]]),
+            debug: response,
+        })
+    }
+}
+
+impl<SPI, E> Transport for SpiTransport<SPI>
+    where
+        SPI: SpiDevice<u8, Error=E>,
+{
+    type Error = E;
+
+    fn read_register(&mut self, addr: u8) -> Result<DataPacket, Error<E>> {
+        // Process cmd to read, return previous (dummy) state
+        let _dummy = self.read_io(addr)?;
+        // Repeat cmd to read, return state
+        self.read_io(addr)
+    }
+
+    fn read_registers<const N: usize>(&mut self, addrs: [u8; N]) -> Result<[DataPacket; N], Error<E>> {
+        read_pipelined(addrs, |addr| self.read_io(addr))
+    }
+
+    fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<E>> {
+        let mut buffer = [addr | 0x80, val[0], val[1], val[2], val[3]];
+
+        let debug_val = buffer;
+
+        self.spi.transfer_in_place(&mut buffer).map_err(Error::Spi)?;
+
+        let response = buffer;
+
+        Ok(DataPacket {
+            status: SpiStatus::from_bytes([response[0]]),
+            data: u32::from_be_bytes([response[1], response[2], response[3], response[4]]),
+            debug: debug_val,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn pipeline_frames() {
+        let frames: Vec<_> = pipeline([0x21, 0x22, 0x23]).collect();
+        assert_eq!(frames, [(0x21, None), (0x22, Some(0)), (0x23, Some(1)), (0x23, Some(2))]);
+        assert_eq!(pipeline([]).count(), 0);
+    }
+
+    #[test]
+    fn read_pipelined_sorts_the_replies() {
+        let mut previous = 0;
+        let packets = read_pipelined::<(), _, 3>([0x21, 0x22, 0x23], |addr| {
+            let packet = DataPacket { data: previous, ..DataPacket::EMPTY };
+            previous = addr as u32;
+            Ok(packet)
+        })
+        .unwrap();
+        assert_eq!(packets.map(|packet| packet.data), [0x21, 0x22, 0x23]);
+    }
+}
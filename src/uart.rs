@@ -0,0 +1,293 @@
+//! Single wire UART transport of the TMC5160
+//!
+//! With SPI_MODE pulled low the TMC5160 is accessed by UART. Every datagram starts with a sync byte and the
+//! node address and ends with a CRC8. Read requests are answered by the addressed node, write accesses are not
+//! answered and are confirmed by reading back the interface transmission counter `IFCNT`.
+
+use core::result::Result;
+
+use embedded_io::{Read, ReadExactError, Write};
+
+use crate::registers::{Address, Registers, SpiStatus};
+use crate::transport::{Interface, Transport};
+use crate::{DataPacket, Error};
+
+/// sync nibble `1010` (sent LSB first) followed by four reserved bits
+const SYNC: u8 = 0x05;
+/// address the TMC5160 uses in its replies
+const MASTER_ADDR: u8 = 0xFF;
+
+/// CRC8 of a datagram (polynomial x^8 + x^2 + x + 1, bytes are processed LSB first)
+pub fn crc8(datagram: &[u8]) -> u8 {
+    let mut crc = 0_u8;
+    for byte in datagram {
+        let mut current = *byte;
+        for _ in 0..8 {
+            if ((crc >> 7) ^ (current & 0x01)) != 0 {
+                crc = (crc << 1) ^ 0x07;
+            } else {
+                crc <<= 1;
+            }
+            current >>= 1;
+        }
+    }
+    crc
+}
+
+/// UART transport talking to a single node
+pub struct UartTransport<S> {
+    serial: S,
+    node_addr: u8,
+    single_wire: bool,
+    confirm_writes: bool,
+    ifcnt: Option<u8>,
+}
+
+impl<S> UartTransport<S>
+    where
+        S: Read + Write,
+{
+    /// Create a new transport from a serial port talking to the node at `node_addr`
+    pub fn new(serial: S, node_addr: u8) -> Self {
+        UartTransport {
+            serial,
+            node_addr,
+            single_wire: false,
+            confirm_writes: true,
+            ifcnt: None,
+        }
+    }
+
+    /// TX and RX share a single wire, every sent datagram is read back and checked
+    pub fn single_wire(mut self, single_wire: bool) -> Self {
+        self.single_wire = single_wire;
+        self
+    }
+
+    /// confirm every write by reading back IFCNT (Default is true)
+    pub fn confirm_writes(mut self, confirm: bool) -> Self {
+        self.confirm_writes = confirm;
+        self
+    }
+
+    /// address of the node this transport talks to
+    pub fn node_addr(&self) -> u8 {
+        self.node_addr
+    }
+
+    /// release the serial port
+    pub fn release(self) -> S {
+        self.serial
+    }
+
+    /// read the interface transmission counter IFCNT, it is incremented with every successful write
+    pub fn read_ifcnt(&mut self) -> Result<u8, Error<S::Error>> {
+        let reply = self.request(Registers::IFCNT.addr())?;
+        Ok(reply[6])
+    }
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), Error<S::Error>> {
+        self.serial.write_all(datagram).map_err(Error::Uart)?;
+        self.serial.flush().map_err(Error::Uart)?;
+        if self.single_wire {
+            // the datagram shows up on our own RX line, drop it before the reply
+            let mut buffer = [0; 8];
+            let echo = &mut buffer[..datagram.len()];
+            self.receive(echo)?;
+            if echo != datagram {
+                return Err(Error::Echo);
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<(), Error<S::Error>> {
+        self.serial.read_exact(buffer).map_err(|err| match err {
+            ReadExactError::UnexpectedEof => Error::NoReply,
+            ReadExactError::Other(err) => Error::Uart(err),
+        })
+    }
+
+    fn request(&mut self, addr: u8) -> Result<[u8; 8], Error<S::Error>> {
+        let addr = addr & 0x7F;
+        let mut request = [SYNC, self.node_addr, addr, 0];
+        request[3] = crc8(&request[..3]);
+        self.send(&request)?;
+
+        let mut reply = [0; 8];
+        self.receive(&mut reply)?;
+        if reply[7] != crc8(&reply[..7]) {
+            return Err(Error::Crc);
+        }
+        if reply[0] != SYNC || reply[1] != MASTER_ADDR || reply[2] != addr {
+            return Err(Error::InvalidReply);
+        }
+        Ok(reply)
+    }
+}
+
+impl<S> Transport for UartTransport<S>
+    where
+        S: Read + Write,
+{
+    type Error = S::Error;
+
+    fn interface(&self) -> Interface {
+        Interface::Uart
+    }
+
+    fn read_register(&mut self, addr: u8) -> Result<DataPacket, Error<S::Error>> {
+        let reply = self.request(addr)?;
+        let mut debug_val: [u8; 5] = [0; 5];
+        debug_val.copy_from_slice(&reply[2..7]);
+        // there is no status byte in UART mode
+        Ok(DataPacket { status: SpiStatus::new(), data: u32::from_be_bytes([reply[3], reply[4], reply[5], reply[6]]), debug: debug_val })
+    }
+
+    fn write_register(&mut self, addr: u8, val: [u8; 4]) -> Result<DataPacket, Error<S::Error>> {
+        let ifcnt = if self.confirm_writes {
+            match self.ifcnt {
+                Some(ifcnt) => ifcnt,
+                None => self.read_ifcnt()?,
+            }
+        } else {
+            0
+        };
+
+        let mut datagram = [SYNC, self.node_addr, addr | 0x80, val[0], val[1], val[2], val[3], 0];
+        datagram[7] = crc8(&datagram[..7]);
+        self.send(&datagram)?;
+
+        if addr & 0x7F == Registers::SLAVECONF.addr() {
+            // the node answers on its new address from now on
+            self.node_addr = val[3];
+        }
+
+        if self.confirm_writes {
+            let next = self.read_ifcnt()?;
+            if next != ifcnt.wrapping_add(1) {
+                self.ifcnt = None;
+                return Err(Error::WriteNotConfirmed);
+            }
+            self.ifcnt = Some(next);
+        }
+
+        let mut debug_val: [u8; 5] = [0; 5];
+        debug_val.copy_from_slice(&datagram[2..7]);
+        Ok(DataPacket { status: SpiStatus::new(), data: 0, debug: debug_val })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::convert::Infallible;
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// serial port that records the sent bytes and answers with prepared bytes
+    struct Serial {
+        tx: Vec<u8>,
+        rx: Vec<u8>,
+    }
+
+    impl embedded_io::ErrorType for Serial {
+        type Error = Infallible;
+    }
+
+    impl Read for Serial {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            let len = buf.len().min(self.rx.len());
+            buf[..len].copy_from_slice(&self.rx[..len]);
+            self.rx.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl Write for Serial {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    fn reply(addr: u8, data: u32) -> [u8; 8] {
+        let data = data.to_be_bytes();
+        let mut reply = [SYNC, MASTER_ADDR, addr, data[0], data[1], data[2], data[3], 0];
+        reply[7] = crc8(&reply[..7]);
+        reply
+    }
+
+    fn transport(rx: &[u8]) -> UartTransport<Serial> {
+        UartTransport::new(Serial { tx: Vec::new(), rx: rx.to_vec() }, 0)
+    }
+
+    #[test]
+    fn crc8_of_datasheet_example() {
+        assert_eq!(crc8(&[0x05, 0x00, 0x00]), 0x48);
+    }
+
+    #[test]
+    fn read_request_and_reply() {
+        let mut uart = transport(&reply(0x21, 0x1234_5678));
+        let packet = uart.read_register(0x21).unwrap();
+        assert_eq!(packet.data, 0x1234_5678);
+        let serial = uart.release();
+        assert_eq!(serial.tx, [SYNC, 0, 0x21, crc8(&[SYNC, 0, 0x21])]);
+    }
+
+    #[test]
+    fn reply_with_wrong_crc() {
+        let mut reply = reply(0x21, 1);
+        reply[7] ^= 1;
+        assert!(matches!(transport(&reply).read_register(0x21), Err(Error::Crc)));
+    }
+
+    #[test]
+    fn reply_for_other_register() {
+        assert!(matches!(transport(&reply(0x22, 1)).read_register(0x21), Err(Error::InvalidReply)));
+    }
+
+    #[test]
+    fn missing_reply() {
+        assert!(matches!(transport(&[]).read_register(0x21), Err(Error::NoReply)));
+    }
+
+    #[test]
+    fn single_wire_echo() {
+        let request = [SYNC, 0, 0x21, crc8(&[SYNC, 0, 0x21])];
+        let mut rx = request.to_vec();
+        rx.extend_from_slice(&reply(0x21, 7));
+        let mut uart = transport(&rx).single_wire(true);
+        assert_eq!(uart.read_register(0x21).unwrap().data, 7);
+
+        let mut rx = [SYNC, 0, 0x22, 0].to_vec();
+        rx.extend_from_slice(&reply(0x21, 7));
+        let mut uart = transport(&rx).single_wire(true);
+        assert!(matches!(uart.read_register(0x21), Err(Error::Echo)));
+    }
+
+    #[test]
+    fn write_is_confirmed_by_ifcnt() {
+        let ifcnt = Registers::IFCNT.addr();
+        let mut rx = reply(ifcnt, 4).to_vec();
+        rx.extend_from_slice(&reply(ifcnt, 5));
+        let mut uart = transport(&rx);
+        uart.write_register(0x6C, 0x1041_0150_u32.to_be_bytes()).unwrap();
+        let serial = uart.release();
+        let mut datagram = [SYNC, 0, 0xEC, 0x10, 0x41, 0x01, 0x50, 0];
+        datagram[7] = crc8(&datagram[..7]);
+        assert_eq!(serial.tx[4..12], datagram);
+
+        let mut rx = reply(ifcnt, 4).to_vec();
+        rx.extend_from_slice(&reply(ifcnt, 4));
+        let mut uart = transport(&rx);
+        assert!(matches!(uart.write_register(0x6C, [0; 4]), Err(Error::WriteNotConfirmed)));
+    }
+}
@@ -0,0 +1,299 @@
+//! Units of positions and velocities
+//!
+//! The integer types hold the register values without any conversion, so positions never lose microstep precision.
+//! The physical units are converted with the [`Mechanics`] of the axis.
+
+use crate::Error;
+
+/// position in microsteps as in XACTUAL, XTARGET, XLATCH and X_ENC
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Microsteps(pub i32);
+
+/// velocity as in VACTUAL and VMAX in microsteps per t = 2^24 / fCLK
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VelocityRaw(pub i32);
+
+impl VelocityRaw {
+    /// sign extend the 24 bit VACTUAL register value
+    pub fn from_vactual(vactual: u32) -> Self {
+        VelocityRaw(((vactual << 8) as i32) >> 8)
+    }
+}
+
+/// distance in millimeters after the lead screw
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Millimeters(pub f32);
+
+/// velocity in millimeters per second after the lead screw
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct MmPerSec(pub f32);
+
+/// acceleration in millimeters per second squared after the lead screw
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct MmPerSec2(pub f32);
+
+/// angle in degrees after the gear
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Degrees(pub f32);
+
+/// angular velocity in degrees per second after the gear
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct DegreesPerSec(pub f32);
+
+/// angular acceleration in degrees per second squared after the gear
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct DegreesPerSec2(pub f32);
+
+/// motor and transmission between the microsteps of the driver and the physical units
+///
+/// The gear ratio is the number of motor revolutions per revolution of the output, the lead is the travel in
+/// millimeters per revolution of the output. Linear units can only be converted with a lead screw.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mechanics {
+    /// full steps per revolution of the motor
+    pub full_steps_per_rev: u32,
+    /// microsteps per full step
+    pub microsteps: u32,
+    /// motor revolutions per output revolution
+    pub gear_ratio: f32,
+    /// millimeters per output revolution, 0 without a lead screw
+    pub lead: f32,
+}
+
+impl Default for Mechanics {
+    /// a 200 step motor with 256 microsteps directly on the output
+    fn default() -> Self {
+        Mechanics::new(200, 256)
+    }
+}
+
+impl Mechanics {
+    /// motor with `full_steps_per_rev` full steps and `microsteps` microsteps per full step directly on the output
+    pub fn new(full_steps_per_rev: u32, microsteps: u32) -> Self {
+        Mechanics { full_steps_per_rev, microsteps, gear_ratio: 1.0, lead: 0.0 }
+    }
+
+    /// specify the motor revolutions per output revolution (Default is 1)
+    pub fn gear_ratio(mut self, ratio: f32) -> Self {
+        self.gear_ratio = ratio;
+        self
+    }
+
+    /// specify the lead screw pitch in millimeters per output revolution
+    pub fn lead_screw(mut self, pitch: f32) -> Self {
+        self.lead = pitch;
+        self
+    }
+
+    /// microsteps per revolution of the output
+    pub fn microsteps_per_rev(&self) -> f64 {
+        self.full_steps_per_rev as f64 * self.microsteps as f64 * self.gear_ratio as f64
+    }
+
+    /// microsteps per millimeter, `None` without a lead screw
+    pub fn microsteps_per_mm(&self) -> Option<f64> {
+        if self.lead > 0.0 { Some(self.microsteps_per_rev() / self.lead as f64) } else { None }
+    }
+
+    /// microsteps per degree of the output
+    pub fn microsteps_per_degree(&self) -> f64 {
+        self.microsteps_per_rev() / 360.0
+    }
+}
+
+/// a position or distance that can be converted to microsteps
+///
+/// `f32` and `i32` are full steps of the motor. The conversions fail with [`Error::UnitConversion`] if the mechanics
+/// can not represent the unit and with [`Error::OutOfRange`] if the position does not fit 32 bits in microsteps.
+pub trait Position: Sized {
+    /// convert to microsteps
+    fn to_microsteps<E>(self, mechanics: &Mechanics) -> Result<Microsteps, Error<E>>;
+    /// convert from microsteps
+    fn from_microsteps<E>(position: Microsteps, mechanics: &Mechanics) -> Result<Self, Error<E>>;
+}
+
+/// a velocity that can be converted to microsteps per second
+///
+/// `f32` is full steps of the motor per second.
+pub trait Velocity: Sized {
+    /// convert to microsteps per second, `None` if the mechanics can not represent the unit
+    fn to_microsteps_per_sec(self, mechanics: &Mechanics) -> Option<f32>;
+    /// convert from microsteps per second, `None` if the mechanics can not represent the unit
+    fn from_microsteps_per_sec(velocity: f32, mechanics: &Mechanics) -> Option<Self>;
+}
+
+/// an acceleration that can be converted to microsteps per second squared
+///
+/// `f32` is full steps of the motor per second squared.
+pub trait Acceleration: Sized {
+    /// convert to microsteps per second squared, `None` if the mechanics can not represent the unit
+    fn to_microsteps_per_sec2(self, mechanics: &Mechanics) -> Option<f32>;
+}
+
+/// truncate a position in microsteps, fails with [`Error::OutOfRange`] if it does not fit 32 bits
+fn microsteps<E>(position: f64) -> Result<Microsteps, Error<E>> {
+    if (i32::MIN as f64..=i32::MAX as f64).contains(&position) {
+        Ok(Microsteps(position as i32))
+    } else {
+        Err(Error::OutOfRange)
+    }
+}
+
+impl Position for Microsteps {
+    fn to_microsteps<E>(self, _mechanics: &Mechanics) -> Result<Microsteps, Error<E>> {
+        Ok(self)
+    }
+
+    fn from_microsteps<E>(position: Microsteps, _mechanics: &Mechanics) -> Result<Self, Error<E>> {
+        Ok(position)
+    }
+}
+
+impl Position for f32 {
+    fn to_microsteps<E>(self, mechanics: &Mechanics) -> Result<Microsteps, Error<E>> {
+        microsteps(self as f64 * mechanics.microsteps as f64)
+    }
+
+    fn from_microsteps<E>(position: Microsteps, mechanics: &Mechanics) -> Result<Self, Error<E>> {
+        Ok((position.0 as f64 / mechanics.microsteps as f64) as f32)
+    }
+}
+
+impl Position for i32 {
+    fn to_microsteps<E>(self, mechanics: &Mechanics) -> Result<Microsteps, Error<E>> {
+        let position = self as i64 * mechanics.microsteps as i64;
+        i32::try_from(position).ok().map(Microsteps).ok_or(Error::OutOfRange)
+    }
+
+    fn from_microsteps<E>(position: Microsteps, mechanics: &Mechanics) -> Result<Self, Error<E>> {
+        let microsteps = i32::try_from(mechanics.microsteps).map_err(|_| Error::UnitConversion)?;
+        position.0.checked_div(microsteps).ok_or(Error::UnitConversion)
+    }
+}
+
+impl Position for Millimeters {
+    fn to_microsteps<E>(self, mechanics: &Mechanics) -> Result<Microsteps, Error<E>> {
+        microsteps(self.0 as f64 * mechanics.microsteps_per_mm().ok_or(Error::UnitConversion)?)
+    }
+
+    fn from_microsteps<E>(position: Microsteps, mechanics: &Mechanics) -> Result<Self, Error<E>> {
+        let microsteps_per_mm = mechanics.microsteps_per_mm().ok_or(Error::UnitConversion)?;
+        Ok(Millimeters((position.0 as f64 / microsteps_per_mm) as f32))
+    }
+}
+
+impl Position for Degrees {
+    fn to_microsteps<E>(self, mechanics: &Mechanics) -> Result<Microsteps, Error<E>> {
+        microsteps(self.0 as f64 * mechanics.microsteps_per_degree())
+    }
+
+    fn from_microsteps<E>(position: Microsteps, mechanics: &Mechanics) -> Result<Self, Error<E>> {
+        Ok(Degrees((position.0 as f64 / mechanics.microsteps_per_degree()) as f32))
+    }
+}
+
+impl Velocity for f32 {
+    fn to_microsteps_per_sec(self, mechanics: &Mechanics) -> Option<f32> {
+        Some(self * mechanics.microsteps as f32)
+    }
+
+    fn from_microsteps_per_sec(velocity: f32, mechanics: &Mechanics) -> Option<Self> {
+        Some(velocity / mechanics.microsteps as f32)
+    }
+}
+
+impl Velocity for MmPerSec {
+    fn to_microsteps_per_sec(self, mechanics: &Mechanics) -> Option<f32> {
+        Some(self.0 * mechanics.microsteps_per_mm()? as f32)
+    }
+
+    fn from_microsteps_per_sec(velocity: f32, mechanics: &Mechanics) -> Option<Self> {
+        Some(MmPerSec(velocity / mechanics.microsteps_per_mm()? as f32))
+    }
+}
+
+impl Velocity for DegreesPerSec {
+    fn to_microsteps_per_sec(self, mechanics: &Mechanics) -> Option<f32> {
+        Some(self.0 * mechanics.microsteps_per_degree() as f32)
+    }
+
+    fn from_microsteps_per_sec(velocity: f32, mechanics: &Mechanics) -> Option<Self> {
+        Some(DegreesPerSec(velocity / mechanics.microsteps_per_degree() as f32))
+    }
+}
+
+impl Acceleration for f32 {
+    fn to_microsteps_per_sec2(self, mechanics: &Mechanics) -> Option<f32> {
+        Some(self * mechanics.microsteps as f32)
+    }
+}
+
+impl Acceleration for MmPerSec2 {
+    fn to_microsteps_per_sec2(self, mechanics: &Mechanics) -> Option<f32> {
+        Some(self.0 * mechanics.microsteps_per_mm()? as f32)
+    }
+}
+
+impl Acceleration for DegreesPerSec2 {
+    fn to_microsteps_per_sec2(self, mechanics: &Mechanics) -> Option<f32> {
+        Some(self.0 * mechanics.microsteps_per_degree() as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Result<T> = core::result::Result<T, Error<()>>;
+
+    #[test]
+    fn full_steps() {
+        let mechanics = Mechanics::default();
+        assert_eq!(1.5_f32.to_microsteps::<()>(&mechanics).unwrap(), Microsteps(384));
+        assert_eq!((-3).to_microsteps::<()>(&mechanics).unwrap(), Microsteps(-768));
+        assert_eq!(<f32 as Position>::from_microsteps::<()>(Microsteps(-384), &mechanics).unwrap(), -1.5);
+        assert_eq!(<i32 as Position>::from_microsteps::<()>(Microsteps(-383), &mechanics).unwrap(), -1);
+        let no_microsteps: Result<i32> = Position::from_microsteps(Microsteps(1), &Mechanics::new(200, 0));
+        assert!(matches!(no_microsteps, Err(Error::UnitConversion)));
+    }
+
+    #[test]
+    fn full_steps_out_of_range() {
+        let mechanics = Mechanics::default();
+        assert_eq!((-(1 << 23)).to_microsteps::<()>(&mechanics).unwrap(), Microsteps(i32::MIN));
+        let wrapped: Result<Microsteps> = (1 << 23).to_microsteps(&mechanics);
+        assert!(matches!(wrapped, Err(Error::OutOfRange)));
+        let saturated: Result<Microsteps> = 1e7_f32.to_microsteps(&mechanics);
+        assert!(matches!(saturated, Err(Error::OutOfRange)));
+        let nan: Result<Microsteps> = f32::NAN.to_microsteps(&mechanics);
+        assert!(matches!(nan, Err(Error::OutOfRange)));
+    }
+
+    #[test]
+    fn millimeters() {
+        let mechanics = Mechanics::default().lead_screw(8.0);
+        assert_eq!(Millimeters(12.5).to_microsteps::<()>(&mechanics).unwrap(), Microsteps(80_000));
+        assert_eq!(Millimeters::from_microsteps::<()>(Microsteps(-80_000), &mechanics).unwrap(), Millimeters(-12.5));
+        let out_of_range: Result<Microsteps> = Millimeters(1e6).to_microsteps(&mechanics);
+        assert!(matches!(out_of_range, Err(Error::OutOfRange)));
+        let no_lead_screw: Result<Microsteps> = Millimeters(1.0).to_microsteps(&Mechanics::default());
+        assert!(matches!(no_lead_screw, Err(Error::UnitConversion)));
+    }
+
+    #[test]
+    fn degrees() {
+        let mechanics = Mechanics::default().gear_ratio(3.0);
+        assert_eq!(Degrees(90.0).to_microsteps::<()>(&mechanics).unwrap(), Microsteps(38_400));
+        assert_eq!(Degrees::from_microsteps::<()>(Microsteps(-38_400), &mechanics).unwrap(), Degrees(-90.0));
+        let out_of_range: Result<Microsteps> = Degrees(-1e7).to_microsteps(&mechanics);
+        assert!(matches!(out_of_range, Err(Error::OutOfRange)));
+    }
+
+    #[test]
+    fn velocity_raw_sign_extension() {
+        assert_eq!(VelocityRaw::from_vactual(0x7F_FFFF), VelocityRaw((1 << 23) - 1));
+        assert_eq!(VelocityRaw::from_vactual(0x80_0000), VelocityRaw(-(1 << 23)));
+        assert_eq!(VelocityRaw::from_vactual(0xFF_FFFF), VelocityRaw(-1));
+        assert_eq!(VelocityRaw::from_vactual(0xFF00_0001), VelocityRaw(1));
+    }
+}